@@ -0,0 +1,165 @@
+//! A subset of git's "approxidate" relative dates, as accepted by
+//! `git log --since`: `3 days ago`, `3.days.ago`, `yesterday`,
+//! `last monday`, `last week`, `noon`, `midnight` and combinations like
+//! `yesterday noon`.
+
+use chrono::{DateTime, Datelike, Local, Months, NaiveTime, TimeDelta, TimeZone, Weekday};
+
+/// Resolve a relative date against `now`, `None` if any word is unknown.
+pub fn parse(s: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let s = s.to_lowercase();
+    let mut words = s
+        .split(|c: char| c.is_whitespace() || c == '.' || c == '_')
+        .filter(|w| !w.is_empty())
+        .peekable();
+    let mut time = now;
+    let mut matched = false;
+    while let Some(word) = words.next() {
+        match word {
+            "now" | "today" | "ago" => {}
+            "yesterday" => time = back(time, 1, Unit::Day)?,
+            "midnight" => time = at(time, 0)?,
+            "noon" => {
+                // A bare `noon` is the latest one, `yesterday noon` is that day's.
+                if time == now && time.time() < NaiveTime::from_hms_opt(12, 0, 0)? {
+                    time = back(time, 1, Unit::Day)?;
+                }
+                time = at(time, 12)?;
+            }
+            "last" => {
+                let next = words.next()?;
+                if let Some(weekday) = weekday(next) {
+                    time = last_weekday(time, weekday, true)?;
+                } else {
+                    time = back(time, 1, unit(next)?)?;
+                }
+            }
+            _ => {
+                if let Some(weekday) = weekday(word) {
+                    time = last_weekday(time, weekday, false)?;
+                } else {
+                    let n: u32 = word.parse().ok()?;
+                    time = back(time, n, unit(words.next()?)?)?;
+                }
+            }
+        }
+        matched = true;
+    }
+    matched.then_some(time)
+}
+
+#[derive(Copy, Clone)]
+enum Unit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+fn unit(word: &str) -> Option<Unit> {
+    Some(match word.strip_suffix('s').unwrap_or(word) {
+        "second" | "sec" => Unit::Second,
+        "minute" | "min" => Unit::Minute,
+        "hour" => Unit::Hour,
+        "day" => Unit::Day,
+        "week" => Unit::Week,
+        "month" => Unit::Month,
+        "year" => Unit::Year,
+        _ => return None,
+    })
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    let day = word.parse::<Weekday>().ok()?;
+    // chrono also takes three-letter names, git needs at least that much too.
+    (word.len() >= 3).then_some(day)
+}
+
+/// `n` units before `time`, months and years keep the day of month.
+///
+/// `None` when the result is out of chrono's range.
+fn back(time: DateTime<Local>, n: u32, unit: Unit) -> Option<DateTime<Local>> {
+    let n64 = i64::from(n);
+    let duration = match unit {
+        Unit::Second => TimeDelta::try_seconds(n64)?,
+        Unit::Minute => TimeDelta::try_minutes(n64)?,
+        Unit::Hour => TimeDelta::try_hours(n64)?,
+        Unit::Day => TimeDelta::try_days(n64)?,
+        Unit::Week => TimeDelta::try_weeks(n64)?,
+        Unit::Month => return time.checked_sub_months(Months::new(n)),
+        Unit::Year => return time.checked_sub_months(Months::new(n.checked_mul(12)?)),
+    };
+    time.checked_sub_signed(duration)
+}
+
+/// Midnight of the latest `weekday` up to `time`, before today if `strict`.
+fn last_weekday(time: DateTime<Local>, weekday: Weekday, strict: bool) -> Option<DateTime<Local>> {
+    let mut days = (time.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    if strict && days == 0 {
+        days = 7;
+    }
+    at(back(time, days, Unit::Day)?, 0)
+}
+
+/// `time`'s day at `hour`:00:00.
+fn at(time: DateTime<Local>, hour: u32) -> Option<DateTime<Local>> {
+    let naive = time.date_naive().and_hms_opt(hour, 0, 0)?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday 2024-03-13 15:30 local time.
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 13, 15, 30, 0).unwrap()
+    }
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn counts_back_units() {
+        assert_eq!(parse("3 days ago", now()), Some(local(2024, 3, 10, 15, 30)));
+        assert_eq!(parse("3.days.ago", now()), Some(local(2024, 3, 10, 15, 30)));
+        assert_eq!(
+            parse("2 hours ago", now()),
+            Some(local(2024, 3, 13, 13, 30))
+        );
+        assert_eq!(
+            parse("1 month ago", now()),
+            Some(local(2024, 2, 13, 15, 30))
+        );
+        assert_eq!(parse("last year", now()), Some(local(2023, 3, 13, 15, 30)));
+    }
+
+    #[test]
+    fn resolves_named_days_and_times() {
+        assert_eq!(
+            parse("yesterday noon", now()),
+            Some(local(2024, 3, 12, 12, 0))
+        );
+        assert_eq!(parse("midnight", now()), Some(local(2024, 3, 13, 0, 0)));
+        assert_eq!(parse("noon", now()), Some(local(2024, 3, 13, 12, 0)));
+        assert_eq!(parse("last monday", now()), Some(local(2024, 3, 11, 0, 0)));
+        assert_eq!(
+            parse("last wednesday", now()),
+            Some(local(2024, 3, 6, 0, 0))
+        );
+        assert_eq!(parse("wednesday", now()), Some(local(2024, 3, 13, 0, 0)));
+    }
+
+    #[test]
+    fn rejects_unknown_words_and_overflows() {
+        assert_eq!(parse("", now()), None);
+        assert_eq!(parse("3 fortnights ago", now()), None);
+        assert_eq!(parse("we", now()), None);
+        assert_eq!(parse("999999999 weeks ago", now()), None);
+        assert_eq!(parse("4294967295 years ago", now()), None);
+    }
+}
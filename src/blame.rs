@@ -0,0 +1,61 @@
+use git2::{
+    Mailmap, ObjectType, Pathspec, PathspecFlags, Repository, TreeWalkMode, TreeWalkResult,
+};
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::Cli;
+
+/// Blame every text file in HEAD, returning surviving lines per group key
+/// and the total number of blamed lines.
+pub fn surviving_lines(
+    repo: &Repository,
+    mailmap: &Mailmap,
+    cli: &Cli,
+) -> Result<(HashMap<String, usize>, usize), git2::Error> {
+    let tree = repo.head()?.peel_to_tree()?;
+    let pathspec = if cli.glob.is_empty() {
+        None
+    } else {
+        Some(Pathspec::new(&cli.glob)?)
+    };
+
+    let mut paths = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            if let Some(name) = entry.name() {
+                paths.push((PathBuf::from(format!("{dir}{name}")), entry.id()));
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+
+    let mut lines = HashMap::new();
+    let mut total = 0;
+    for (path, id) in paths {
+        if let Some(pathspec) = pathspec.as_ref() {
+            if !pathspec.matches_path(&path, PathspecFlags::DEFAULT) {
+                continue;
+            }
+        }
+        if repo.find_blob(id)?.is_binary() {
+            continue;
+        }
+        let blame = repo.blame_file(&path, None)?;
+        for hunk in blame.iter() {
+            let signature = hunk.final_signature();
+            let signature = if cli.no_mailmap {
+                signature
+            } else {
+                mailmap.resolve_signature(&signature)?
+            };
+            // Keyed like the walk, so --name-transform applies here too.
+            let name = cli.transform_name(signature.name().unwrap_or(""));
+            let key = cli.group_key(&name, signature.email().unwrap_or(""));
+            *lines.entry(key).or_insert(0) += hunk.lines_in_hunk();
+            total += hunk.lines_in_hunk();
+        }
+    }
+    Ok((lines, total))
+}
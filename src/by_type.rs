@@ -0,0 +1,120 @@
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::output::{write_json, Format};
+use crate::{add_ratio, ratio, Order, SortBy, Totals};
+
+/// Bucket for messages not following the conventional commits format.
+pub const OTHER: &str = "(other)";
+
+/// Conventional commit type of a message, e.g. `feat` for `feat(cli)!: ...`.
+pub fn commit_type(message: &str) -> &str {
+    let subject = message.lines().next().unwrap_or("");
+    let Some((prefix, _)) = subject.split_once(':') else {
+        return OTHER;
+    };
+    let prefix = prefix.strip_suffix('!').unwrap_or(prefix);
+    let ty = match prefix.split_once('(') {
+        Some((ty, scope)) if scope.ends_with(')') => ty,
+        Some(_) => return OTHER,
+        None => prefix,
+    };
+    if !ty.is_empty() && ty.chars().all(|c| c.is_ascii_lowercase()) {
+        ty
+    } else {
+        OTHER
+    }
+}
+
+/// Bucket record of `--format json` and `jsonl`.
+#[derive(Serialize)]
+struct Row<'a> {
+    #[serde(rename = "type")]
+    ty: &'a str,
+    /// Author or organization with --group-by or --org-map
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<&'a str>,
+    commits: usize,
+    added: usize,
+    deleted: usize,
+}
+
+/// Document of `--format json`.
+#[derive(Serialize)]
+struct Document<'a> {
+    types: Vec<Row<'a>>,
+}
+
+fn mean_commit(totals: &Totals) -> f64 {
+    ratio(totals.added + totals.deleted, totals.commits)
+}
+
+/// Write `type[\tauthor]\tcommits\tadded\tdeleted` rows, or with
+/// `--format json` a `types` array and with `jsonl` one object per line.
+///
+/// Other formats are rejected with --by-type when parsing the command line.
+pub fn write(
+    out: &mut dyn Write,
+    stats: HashMap<(String, Option<String>), Totals>,
+    sort_by: SortBy,
+    order: Order,
+    format: Format,
+    pretty: bool,
+) -> io::Result<()> {
+    let mut rows = stats.into_iter().collect::<Vec<_>>();
+    rows.sort_by(|(a_key, a), (b_key, b)| {
+        let cmp = match sort_by {
+            SortBy::Name | SortBy::Email => a_key.cmp(b_key),
+            SortBy::Commits => a.commits.cmp(&b.commits),
+            SortBy::Added => a.added.cmp(&b.added),
+            SortBy::Deleted => a.deleted.cmp(&b.deleted),
+            SortBy::AddRatio => {
+                add_ratio(a.added, a.deleted).total_cmp(&add_ratio(b.added, b.deleted))
+            }
+            // Sizes aren't kept per type, the mean stands in for the median.
+            SortBy::MedianCommit => mean_commit(a).total_cmp(&mean_commit(b)),
+            // Weights aren't kept per type either.
+            SortBy::Weighted => (a.added + a.deleted).cmp(&(b.added + b.deleted)),
+        };
+        match order {
+            Order::Asc => cmp,
+            Order::Desc => cmp.reverse(),
+        }
+        .then_with(|| a_key.cmp(b_key))
+    });
+    if matches!(format, Format::Json | Format::Jsonl) {
+        let rows = rows
+            .iter()
+            .map(|((ty, group), totals)| Row {
+                ty,
+                group: group.as_deref(),
+                commits: totals.commits,
+                added: totals.added,
+                deleted: totals.deleted,
+            })
+            .collect::<Vec<_>>();
+        if format == Format::Json {
+            write_json(out, &Document { types: rows }, pretty)?;
+            return writeln!(out);
+        }
+        for row in rows {
+            write_json(out, &row, pretty)?;
+            writeln!(out)?;
+        }
+        return Ok(());
+    }
+    for ((ty, group), totals) in rows {
+        let Totals {
+            commits,
+            added,
+            deleted,
+        } = totals;
+        match group {
+            Some(group) => writeln!(out, "{ty}\t{group}\t{commits}\t{added}\t{deleted}")?,
+            None => writeln!(out, "{ty}\t{commits}\t{added}\t{deleted}")?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use git2::{Delta, Diff};
+
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Commits touching `--cochange` path A, and those touching B as well.
+#[derive(Default)]
+pub struct CoChange {
+    pub a: usize,
+    pub both: usize,
+}
+
+impl CoChange {
+    /// Count one commit's diff.
+    pub fn add(&mut self, diff: &Diff, a: &str, b: &str) {
+        if touches(diff, a) {
+            self.a += 1;
+            if touches(diff, b) {
+                self.both += 1;
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: CoChange) {
+        self.a += other.a;
+        self.both += other.both;
+    }
+}
+
+/// Whether a diff changes `path` or a file below it.
+fn touches(diff: &Diff, path: &str) -> bool {
+    let path = Path::new(path);
+    diff.deltas()
+        .filter(|d| d.status() != Delta::Unmodified)
+        .any(|d| {
+            [d.old_file().path(), d.new_file().path()]
+                .into_iter()
+                .flatten()
+                .any(|p| p.starts_with(path))
+        })
+}
+
+/// Write `both\tA commits\tshare of A commits touching B`.
+pub fn write(out: &mut dyn Write, counts: &CoChange) -> io::Result<()> {
+    let share = if counts.a == 0 {
+        0.0
+    } else {
+        counts.both as f64 / counts.a as f64 * 100.0
+    };
+    writeln!(out, "{}\t{}\t{share:.1}%", counts.both, counts.a)
+}
@@ -0,0 +1,68 @@
+use git2::{Repository, StatusOptions};
+
+/// Print environment and repository diagnostics for bug reports.
+pub fn print(repo: &Repository) -> Result<(), git2::Error> {
+    let version = git2::Version::get();
+    let (major, minor, patch) = version.libgit2_version();
+    println!("git-stats: {}", env!("CARGO_PKG_VERSION"));
+    println!("git2: {}", version.crate_version());
+    println!("libgit2: {major}.{minor}.{patch}");
+    println!("repository: {}", repo.path().display());
+
+    let head = repo.head().ok();
+    let branch = head
+        .as_ref()
+        .and_then(|h| h.shorthand())
+        .unwrap_or("(detached or unborn)");
+    println!("head: {branch}");
+    let default_branch = repo
+        .find_reference("refs/remotes/origin/HEAD")
+        .ok()
+        .and_then(|r| r.symbolic_target().map(String::from))
+        .map(|t| t.trim_start_matches("refs/remotes/origin/").to_string());
+    println!(
+        "default branch: {}",
+        default_branch.as_deref().unwrap_or("(unknown)")
+    );
+
+    let mailmap_file = repo
+        .workdir()
+        .is_some_and(|dir| dir.join(".mailmap").exists());
+    let mailmap_blob = head
+        .as_ref()
+        .and_then(|h| h.peel_to_tree().ok())
+        .is_some_and(|tree| tree.get_name(".mailmap").is_some());
+    let mailmap_config = repo.config()?.get_path("mailmap.file").is_ok();
+    println!(
+        "mailmap: {}",
+        if mailmap_file || mailmap_blob || mailmap_config {
+            "yes"
+        } else {
+            "no"
+        }
+    );
+
+    let mut revwalk = repo.revwalk()?;
+    let commits = match revwalk.push_head() {
+        Ok(()) => revwalk.count().to_string(),
+        Err(_) => "(no HEAD)".to_string(),
+    };
+    println!("commits reachable from HEAD: {commits}");
+
+    let dirty = if repo.is_bare() {
+        "bare".to_string()
+    } else {
+        let statuses = repo.statuses(Some(
+            StatusOptions::new()
+                .include_untracked(true)
+                .include_ignored(false),
+        ))?;
+        if statuses.is_empty() {
+            "clean".to_string()
+        } else {
+            format!("dirty ({} entries)", statuses.len())
+        }
+    };
+    println!("working tree: {dirty}");
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+use std::process::ExitCode;
+
+/// Errors surfaced by git-stats, each with a distinct exit code:
+///
+/// | code | meaning                               |
+/// |------|---------------------------------------|
+/// | 1    | other git or I/O failure              |
+/// | 2    | invalid command line (from clap)      |
+/// | 3    | repository could not be opened        |
+/// | 4    | a ref or commit could not be resolved |
+/// | 5    | a time argument could not be parsed   |
+/// | 6    | no commits matched the filters        |
+#[derive(Debug)]
+pub enum AppError {
+    RepoOpen(git2::Error),
+    RefResolve(String),
+    TimeParse(String),
+    EmptyResult,
+    Git(git2::Error),
+    Io(std::io::Error),
+    Other(String),
+}
+
+impl AppError {
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            AppError::Git(_) | AppError::Io(_) | AppError::Other(_) => 1,
+            AppError::RepoOpen(_) => 3,
+            AppError::RefResolve(_) => 4,
+            AppError::TimeParse(_) => 5,
+            AppError::EmptyResult => 6,
+        })
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::RepoOpen(e) => write!(f, "cannot open repository: {}", e.message()),
+            AppError::RefResolve(msg) => write!(f, "cannot resolve {msg}"),
+            AppError::TimeParse(msg) => write!(f, "{msg}"),
+            AppError::EmptyResult => write!(f, "no commits matched"),
+            AppError::Git(e) => write!(f, "{}", e.message()),
+            AppError::Io(e) => write!(f, "{e}"),
+            AppError::Other(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<git2::Error> for AppError {
+    fn from(e: git2::Error) -> Self {
+        AppError::Git(e)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e)
+    }
+}
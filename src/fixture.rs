@@ -0,0 +1,112 @@
+//! Scripted repositories for tests.
+
+use git2::{Oid, Repository, Signature, Time};
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::Path;
+
+/// A repository in a temporary directory, removed on drop.
+pub struct Fixture {
+    // Kept for its drop.
+    _dir: TempDir,
+    pub repo: Repository,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        Fixture { _dir: dir, repo }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.repo.workdir().unwrap()
+    }
+
+    /// Commit `files` as `(path, content)` pairs on top of HEAD, authored and
+    /// committed by `author <email>` at `timestamp` (UTC seconds).
+    ///
+    /// Files not listed are kept from the parent commit.
+    pub fn commit(&self, author: &str, email: &str, files: &[(&str, &str)], timestamp: i64) -> Oid {
+        let message = format!(
+            "change {}",
+            files.iter().map(|f| f.0).collect::<Vec<_>>().join(", ")
+        );
+        self.commit_message(author, email, files, &message, timestamp)
+    }
+
+    /// [`Fixture::commit`] with `message`.
+    pub fn commit_message(
+        &self,
+        author: &str,
+        email: &str,
+        files: &[(&str, &str)],
+        message: &str,
+        timestamp: i64,
+    ) -> Oid {
+        let mut index = self.repo.index().unwrap();
+        for (path, content) in files {
+            let full = self.path().join(path);
+            if let Some(parent) = full.parent() {
+                fs::create_dir_all(parent).unwrap();
+            }
+            fs::write(full, content).unwrap();
+            index.add_path(Path::new(path)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::new(author, email, &Time::new(timestamp, 0)).unwrap();
+        let parent = self
+            .repo
+            .head()
+            .ok()
+            .map(|head| head.peel_to_commit().unwrap());
+        self.repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parent.iter().collect::<Vec<_>>(),
+            )
+            .unwrap()
+    }
+
+    /// Commit the tree of the last of `parents` with `message`, authored and
+    /// committed by `author <email>` at `timestamp`, and move HEAD to it.
+    pub fn merge(
+        &self,
+        author: &str,
+        email: &str,
+        parents: &[Oid],
+        message: &str,
+        timestamp: i64,
+    ) -> Oid {
+        let parents = parents
+            .iter()
+            .map(|oid| self.repo.find_commit(*oid).unwrap())
+            .collect::<Vec<_>>();
+        let tree = parents.last().unwrap().tree().unwrap();
+        let signature = Signature::new(author, email, &Time::new(timestamp, 0)).unwrap();
+        let oid = self
+            .repo
+            .commit(
+                None,
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents.iter().collect::<Vec<_>>(),
+            )
+            .unwrap();
+        self.repo.head().unwrap().set_target(oid, "merge").unwrap();
+        oid
+    }
+
+    /// Write a `.mailmap` to the work tree, where libgit2 reads it from.
+    pub fn mailmap(&self, content: &str) {
+        fs::write(self.path().join(".mailmap"), content).unwrap();
+    }
+}
@@ -0,0 +1,34 @@
+use std::io::{self, Write};
+
+use crate::User;
+
+/// Write the longest gap in days between consecutive commits of each
+/// author, with the dates bounding it; single-commit authors show `n/a`.
+pub fn write(out: &mut dyn Write, stats: &[User]) -> io::Result<()> {
+    for user in stats {
+        let mut times = user.times.clone();
+        times.sort();
+        let gap = times
+            .windows(2)
+            .map(|w| (w[1] - w[0], w[0], w[1]))
+            .max_by_key(|(gap, _, _)| *gap);
+        match gap {
+            Some((gap, from, to)) => writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                user.name,
+                user.email,
+                user.commits,
+                gap.num_days(),
+                from.format("%Y-%m-%d"),
+                to.format("%Y-%m-%d"),
+            )?,
+            None => writeln!(
+                out,
+                "{}\t{}\t{}\tn/a\t\t",
+                user.name, user.email, user.commits
+            )?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Stderr verbosity, results on stdout are never affected.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[repr(u8)]
+pub enum Level {
+    /// Nothing but fatal errors
+    Quiet = 0,
+    /// Warnings
+    Warn = 1,
+    /// Progress and summaries, `-v`
+    Info = 2,
+    /// Per-commit details, `-vv`
+    Debug = 3,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Warn as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level as u8
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::Level::Warn) {
+            eprintln!("warning: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::Level::Info) {
+            eprintln!("{}", format_args!($($arg)*));
+        }
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::Level::Debug) {
+            eprintln!("debug: {}", format_args!($($arg)*));
+        }
+    };
+}
@@ -1,29 +1,138 @@
-use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone};
-use clap::{Parser, ValueEnum};
-use git2::{DiffOptions, Repository};
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone, Weekday};
+use clap::error::{ContextKind, ErrorKind};
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use git2::{
+    Commit, Delta, DescribeFormatOptions, DescribeOptions, Diff, DiffFindOptions, DiffOptions, Oid,
+    Patch, Pathspec, PathspecFlags, Repository, Signature, Sort,
+};
+use regex::Regex;
 
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
 
-#[derive(Parser)]
+#[macro_use]
+mod logging;
+mod approxidate;
+mod blame;
+mod by_type;
+mod cochange;
+mod doctor;
+mod error;
+#[cfg(test)]
+mod fixture;
+mod gaps;
+mod matrix;
+mod net;
+mod newcomers;
+mod org;
+mod output;
+mod size;
+mod template;
+mod timeseries;
+#[cfg(feature = "tui")]
+mod tui;
+mod type_changes;
+mod work_hours;
+
+use error::AppError;
+use output::Format;
+use template::{parse_template, Template};
+use timeseries::Period;
+
+#[derive(Parser, Clone, Debug)]
 #[command(version, about, long_about = None)]
 struct Cli {
     /// Glob paths
     glob: Vec<String>,
-    /// Repository path
+    /// Repository path, repeat to report across several repositories
     #[arg(short, long, value_name = "PATH")]
-    repository: Option<String>,
+    repository: Vec<String>,
+    /// Repositories scanned in parallel, defaults to the number of CPUs
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+
+    /// Only count commits not reachable from this ref, like `START..END`
+    #[arg(long, value_name = "REF")]
+    start_ref: Option<String>,
+    /// Only count the commits of the branch since it forked from BASE, like `BASE...END`.
+    ///
+    /// The walk starts after the merge base of BASE and the analyzed ref,
+    /// shown by --explain.
+    #[arg(long, value_name = "BASE", conflicts_with = "start_ref")]
+    merge_base: Option<String>,
+    /// Walk from this ref instead of HEAD
+    #[arg(long, value_name = "REF", visible_alias = "ref")]
+    end_ref: Option<String>,
+
+    /// Search the repository path and its parents for a repository, like git does
+    #[arg(long, default_value = "false")]
+    discover: bool,
 
     /// Module name
     #[arg(short, long)]
     module: Option<String>,
 
-    /// Start time
-    #[arg(short, long, value_name = "DATETIME", value_parser = parse_time)]
+    /// Start time, falls back to $GIT_STATS_SINCE
+    #[arg(short, long, value_name = "DATETIME", value_parser = parse_time, env = "GIT_STATS_SINCE")]
     since: Option<DateTime<Local>>,
 
-    /// End time
-    #[arg(short, long, value_name = "DATETIME", value_parser = parse_time)]
+    /// Set --since to the earliest commit, so reports record an explicit range.
+    ///
+    /// Takes precedence over $GIT_STATS_SINCE.
+    #[arg(long, default_value = "false")]
+    since_first_commit: bool,
+
+    /// Set --since to the time of the commit a tag points to, annotated tags included
+    #[arg(long, value_name = "TAG", conflicts_with = "since_first_commit")]
+    since_tag: Option<String>,
+
+    /// Set --since to the most recent tag reachable from the analyzed ref, i.e. the last release
+    #[arg(long, default_value = "false", conflicts_with_all = ["since_first_commit", "since_tag"])]
+    since_last_tag: bool,
+
+    /// End time, falls back to $GIT_STATS_UNTIL
+    #[arg(short, long, value_name = "DATETIME", value_parser = parse_time, env = "GIT_STATS_UNTIL")]
     until: Option<DateTime<Local>>,
+    /// Exclude commits at exactly --until, so adjacent ranges don't overlap.
+    ///
+    /// --since is always inclusive and --until inclusive by default; with
+    /// this flag a range is `[since, until)`, and month-by-month runs like
+    /// `--since 2024-01-01 --until 2024-02-01` count every commit once.
+    #[arg(long, default_value = "false")]
+    half_open: bool,
+
+    /// Set --until to the time of the commit a tag points to, annotated tags included
+    #[arg(long, value_name = "TAG")]
+    until_tag: Option<String>,
+
+    /// Fail when an e-mail is used with several names or a name with several
+    /// e-mails after mailmap resolution, a sign of missing mailmap entries
+    #[arg(long, default_value = "false")]
+    strict_identity: bool,
+
+    /// Group commits by author name (default), e-mail or full identity
+    #[arg(long, value_enum)]
+    group_by: Option<GroupBy>,
+    /// Aggregate by organization, mapped from e-mails and domains in FILE.
+    ///
+    /// FILE has one `KEY,ORG` line per entry, KEY being an e-mail or a
+    /// domain, with `#` comments. An e-mail entry wins over its domain's;
+    /// unmapped identities are grouped by their e-mail domain, or as
+    /// `(unknown)` without one.
+    #[arg(long, value_name = "FILE", value_parser = org::OrgMap::load, conflicts_with = "group_by")]
+    org_map: Option<org::OrgMap>,
+
+    /// Timestamp used for --since/--until and time bucketing
+    #[arg(long, value_enum, default_value = "commit")]
+    date: DateKind,
 
     /// Sort by field.
     #[arg(long, value_enum, default_value = "commits")]
@@ -33,6 +142,29 @@ struct Cli {
     #[arg(long, value_enum, default_value = "desc")]
     order: Order,
 
+    /// Only show the first N authors after sorting
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+    /// Roll the authors cut by --limit up into a single `(others, M authors)` row
+    #[arg(long, default_value = "false", requires = "limit")]
+    others: bool,
+
+    /// Revision walk order, defaults to libgit2's unsorted walk from HEAD
+    #[arg(long, value_enum)]
+    walk_order: Option<WalkOrder>,
+    /// Stop after walking N commits, before any date or author filter.
+    ///
+    /// Which commits those are depends on the walk order; pair it with
+    /// `--walk-order date` to analyze the N most recent commits.
+    #[arg(long, value_name = "N")]
+    max_commits: Option<usize>,
+    /// Stop walking after SECONDS of wall-clock time and report what was counted so far.
+    ///
+    /// The output is marked partial with a warning, and `partial: true` in
+    /// --format json. The clock is checked every few hundred commits.
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    max_runtime: Option<Duration>,
+
     /// Skip authored by dependabot[bot]
     #[arg(long, default_value = "false")]
     no_bot: bool,
@@ -42,9 +174,446 @@ struct Cli {
     /// Skip authored by ubuntu
     #[arg(long, default_value = "false")]
     no_ubuntu: bool,
+    /// Only count commits whose message matches one of these regexes
+    #[arg(long, value_name = "RE", value_parser = Regex::new)]
+    grep: Vec<Regex>,
+
+    /// Notes ref holding `Author-Override: Name <email>` attribution fixes
+    #[arg(long, value_name = "REF")]
+    use_notes: Option<String>,
+
+    /// Rewrite author names with `REGEX=REPLACEMENT`, may be repeated and
+    /// applied in order after mailmap resolution, e.g. ` \(Contractor\)$=`
+    #[arg(long, value_name = "REGEX=REPLACEMENT", value_parser = parse_name_transform)]
+    name_transform: Vec<NameTransform>,
+
+    /// Use raw commit identities without resolving them through .mailmap.
+    ///
+    /// Useful to audit the mailmap itself; expect more distinct authors than usual.
+    #[arg(long, default_value = "false")]
+    no_mailmap: bool,
+
+    /// Fail if more than this many distinct authors are aggregated.
+    ///
+    /// Guards against a misconfigured mailmap producing a huge number of identities.
+    #[arg(long, value_name = "N")]
+    max_authors: Option<usize>,
+
+    /// Skip this commit, may be repeated
+    #[arg(long, value_name = "OID")]
+    exclude_commit: Vec<String>,
+    /// Skip commits listed in a file, one OID per line (`#` starts a comment)
+    #[arg(long, value_name = "PATH")]
+    exclude_commits_file: Option<String>,
+
+    /// Print only the grand total of the --sort-by field (author count for name/email)
+    #[arg(long, default_value = "false")]
+    output_totals_only: bool,
+
+    /// Print the minimum number of authors accounting for --bus-factor-threshold
+    /// of all added lines; a low number signals concentration risk
+    #[arg(long, default_value = "false")]
+    bus_factor: bool,
+    /// Share of added lines used by --bus-factor, in percent
+    #[arg(long, value_name = "PERCENT", default_value = "50", value_parser = parse_percent)]
+    bus_factor_threshold: f64,
+
+    /// List authors whose e-mail looks unconfigured (no `@`, localhost, (none), ...)
+    #[arg(long, default_value = "false")]
+    report_invalid_emails: bool,
+
+    /// Aggregate by conventional commit type (feat, fix, ...) instead of author,
+    /// crossed with authors when --group-by is given.
+    ///
+    /// Printed as tab separated rows, or as JSON with --format json/jsonl;
+    /// other formats are rejected.
+    #[arg(long, default_value = "false")]
+    by_type: bool,
+
+    /// Rank by a blend of churn and surviving (blamed) lines in HEAD, 0.0-1.0.
+    ///
+    /// score = (1 - w) * churn / total_churn + w * surviving / total_surviving,
+    /// where churn is added + deleted. Overrides --sort-by. The blame pass runs
+    /// once per file in HEAD and can be much slower than the stats walk.
+    #[arg(long, value_name = "WEIGHT", value_parser = parse_weight)]
+    blame_weight: Option<f64>,
+
+    /// Show churn (added + deleted) per 1000 lines of the HEAD tree.
+    ///
+    /// Makes churn comparable between small and large repositories. Counting
+    /// the lines reads every text file in HEAD once.
+    #[arg(long, default_value = "false")]
+    per_kloc: bool,
+
+    /// Report totals per period instead of per author
+    #[arg(long, value_enum, value_name = "PERIOD")]
+    timeseries: Option<Period>,
+    /// Add the tags pointing into each period to --timeseries output
+    #[arg(long, default_value = "false", requires = "timeseries")]
+    annotate_tags: bool,
+
+    /// Count the commits changing both PATH_A and PATH_B instead of per-author stats.
+    ///
+    /// A path matches itself and any file below it. Prints the number of
+    /// commits touching both, the commits touching PATH_A, and the share of
+    /// those that touched PATH_B too; a high share hints at coupling.
+    #[arg(long, num_args = 2, value_names = ["PATH_A", "PATH_B"])]
+    cochange: Option<Vec<String>>,
+
+    /// Print lines changed per file and author as CSV instead of per-author stats.
+    ///
+    /// The long layout (default) has a `file,author,lines` row per pair, the
+    /// wide one a row per file and a column per author. Scope it with GLOB
+    /// on large repositories.
+    #[arg(long, value_enum, value_name = "LAYOUT", num_args = 0..=1, default_missing_value = "long")]
+    by_file_author_matrix: Option<matrix::Layout>,
+
+    /// List the files that changed between text and binary, or file type, per commit.
+    ///
+    /// Prints `commit\tauthor\tpath\tchange` instead of per-author stats;
+    /// accidental binary commits and encoding mistakes often show up here.
+    #[arg(long, default_value = "false")]
+    report_type_changes: bool,
+
+    /// Print the net project change over the date range (files, added, deleted)
+    /// as one diff from the range's base to its tip, instead of per-author churn.
+    ///
+    /// Content added and reverted within the range cancels out, but the
+    /// change can't be attributed to authors.
+    #[arg(long, default_value = "false")]
+    net_within_range: bool,
+
+    /// Report each author's longest gap between consecutive commits
+    #[arg(long, default_value = "false")]
+    gaps: bool,
+
+    /// List newcomers, authors whose first commit in the whole history is on or after DATE.
+    ///
+    /// --since is ignored so earlier commits still count, --until ends the window.
+    #[arg(long, value_name = "DATE", value_parser = parse_time)]
+    new_since: Option<DateTime<Local>>,
+
+    /// Report mean and median commit hour per author instead of line stats
+    #[arg(long, default_value = "false")]
+    work_hours: bool,
+    /// First hour (inclusive) of the working window used by --work-hours
+    #[arg(long, value_name = "HOUR", default_value = "9", value_parser = clap::value_parser!(u32).range(0..24))]
+    work_start: u32,
+    /// Last hour (exclusive) of the working window used by --work-hours
+    #[arg(long, value_name = "HOUR", default_value = "17", value_parser = clap::value_parser!(u32).range(0..=24))]
+    work_end: u32,
+
+    /// Detect renamed files so moves aren't counted as a deletion plus an addition
+    #[arg(long, default_value = "false")]
+    detect_renames: bool,
+    /// Detect files copied from another file, so only the edits to the copy count.
+    ///
+    /// Works on whole files, not moved blocks, and compares against every file in
+    /// the parent tree, which is noticeably slower than --detect-renames. The two
+    /// can be combined.
+    #[arg(long, default_value = "false")]
+    detect_copies: bool,
+    /// Skip rename/copy detection, with a warning, for commits changing more than N files.
+    ///
+    /// Detection compares every added file with every deleted one, so huge
+    /// commits like vendoring sweeps could otherwise stall the walk.
+    #[arg(long, value_name = "N", default_value = "400")]
+    rename_limit: usize,
+    /// Print detected renames per commit to stderr
+    #[arg(long, default_value = "false", requires = "detect_renames")]
+    log_renames: bool,
+
+    /// Skip commits that only delete files, e.g. removing a vendored directory.
+    ///
+    /// Opt-in since it can also hide legitimate cleanups and refactors.
+    #[arg(long, default_value = "false")]
+    exclude_pure_deletions: bool,
+
+    /// Also count the stash list as commits of the configured user.
+    ///
+    /// A stash commit has the stashed HEAD, the index and optionally the
+    /// untracked files as parents; its tree is the stashed working tree, so it
+    /// is diffed against the first parent. Untracked files aren't counted.
+    ///
+    /// Stashes go through the commit filters: --exclude-commit, --sample,
+    /// --max-commits, --since/--until, --grep and the author exclusions.
+    #[arg(long, default_value = "false")]
+    include_stash: bool,
+
+    /// Count commits with the same patch, e.g. cherry-picks, only once.
+    ///
+    /// Commits are matched by `git patch-id`, a hash of the diff ignoring
+    /// whitespace and line numbers, computed over the GLOB-filtered diff. A
+    /// pick with conflict resolutions has another id and is counted again.
+    /// The commit walked first is kept, within one repository.
+    #[arg(long, default_value = "false")]
+    dedup_cherry_picks: bool,
+
+    /// Skip root commits, which often import an existing code base.
+    ///
+    /// Every parentless commit adding more than --initial-threshold lines is
+    /// skipped, including the roots of orphan branches. With the default
+    /// threshold of 0 that's any root commit adding lines at all; raise it,
+    /// e.g. to 1000, to keep small genuine first commits.
+    #[arg(long, default_value = "false")]
+    skip_initial: bool,
+    /// Lines a root commit must add to be skipped by --skip-initial
+    #[arg(long, value_name = "N", default_value = "0", requires = "skip_initial")]
+    initial_threshold: usize,
+
+    /// Count each `* subject` bullet of a squash merge message as a commit.
+    ///
+    /// Best-effort, based only on the message layout GitHub uses; the lines
+    /// are still counted once from the squashed diff.
+    #[arg(long, default_value = "false")]
+    expand_squash: bool,
+
+    /// Skip commits whose subject is a generated merge message, whatever their parents.
+    ///
+    /// Matches `^Merge branch '[^']+'( of \S+)?( into \S+)?$` as written by
+    /// `git merge` and `^Merge pull request #\d+ from \S+$` as written by
+    /// GitHub, so fast-forwarded or rebased integration commits are caught too.
+    #[arg(long, default_value = "false")]
+    exclude_auto_merges: bool,
+
+    /// Count commits whose message has fewer than N characters in a `short_messages` column.
+    ///
+    /// Empty messages always count; merge commits never do, since their
+    /// messages are usually generated.
+    #[arg(long, value_name = "N")]
+    min_message_length: Option<usize>,
+
+    /// Count `Reviewed-by: Name <email>` trailers per identity in a `reviews` column.
+    ///
+    /// Reviewers who never authored a counted commit are listed with 0 commits.
+    #[arg(long, default_value = "false")]
+    credit_reviewers: bool,
+
+    /// Count every commit of each author in the range in an `all_commits` column.
+    ///
+    /// Unlike `commits` it ignores the GLOB paths and counts commits without
+    /// line changes, separating overall activity from activity in the paths.
+    #[arg(long, default_value = "false")]
+    count_all_commits_in_range: bool,
+
+    /// Count merged pull requests per author in a `prs` column.
+    ///
+    /// Best-effort and specific to GitHub: a merge commit whose subject is
+    /// `Merge pull request #N from user/branch` counts one pull request for
+    /// the author of its second parent, the head of the merged branch.
+    /// Squashed and rebased pull requests leave no such merge and aren't counted.
+    #[arg(long, default_value = "false")]
+    count_prs: bool,
+
+    /// Count commits touching more than N files as refactors, in a separate column
+    #[arg(long, value_name = "N")]
+    refactor_threshold: Option<usize>,
+
+    /// Skip files whose full path matches this regex, may be repeated
+    #[arg(long, value_name = "RE", value_parser = Regex::new)]
+    exclude_path_regex: Vec<Regex>,
+
+    /// Weight lines of files matching a pathspec glob, e.g. `src/*=2.0`, may be repeated.
+    ///
+    /// The first matching glob wins, other files weigh 1.0. The sum is shown
+    /// in a `weighted_lines` column and can be sorted by with --sort-by weighted.
+    #[arg(long, value_name = "GLOB=WEIGHT", value_parser = parse_path_weight)]
+    weight_path: Vec<PathWeight>,
+
+    /// Count added and deleted lines whose content matches RE, e.g. `TODO|FIXME`,
+    /// in `grep_added`/`grep_deleted` columns
+    #[arg(long, value_name = "RE", value_parser = Regex::new)]
+    grep_lines: Option<Regex>,
+
+    /// Add `top_file`/`top_file_commits` columns: the file each author changed in the most commits
+    #[arg(long, default_value = "false")]
+    top_file_per_author: bool,
+
+    /// Add an `extensions` column summing lines per file extension, like `rs:+120/-30 yaml:+15/-2`
+    #[arg(long, default_value = "false")]
+    ext_breakdown: bool,
+    /// Extensions shown by --ext-breakdown per author, those with the most lines first
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "3",
+        requires = "ext_breakdown"
+    )]
+    ext_breakdown_limit: usize,
+
+    /// Count lockfile lines (Cargo.lock, package-lock.json, ...) in separate columns
+    #[arg(long, default_value = "false")]
+    separate_lockfiles: bool,
+
+    /// Only process a deterministic PERCENT of commits and scale totals up
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent)]
+    sample: Option<f64>,
+    /// Seed for --sample
+    #[arg(long, default_value = "0")]
+    seed: u64,
+
+    /// Count lines in test files (see --test-pattern) apart from production code
+    #[arg(long, default_value = "false")]
+    split_tests: bool,
+    /// Pathspec globs of test files for --split-tests, may be repeated.
+    ///
+    /// `*` also matches `/`, so `*/tests/*` covers nested test directories.
+    #[arg(
+        long,
+        value_name = "GLOB",
+        default_values = ["tests/*", "*/tests/*", "*_test.*", "test_*.py", "*/test_*.py"]
+    )]
+    test_pattern: Vec<String>,
+
+    /// Show added / (added + deleted) in percent per author
+    #[arg(long, default_value = "false")]
+    show_add_ratio: bool,
+
+    /// Count commits changing both binary and text files per author
+    #[arg(long, default_value = "false")]
+    show_mixed_commits: bool,
+
+    /// Split commits and lines into weekend (Saturday, Sunday) and weekday columns.
+    ///
+    /// Days are taken in the local time zone of the commit times, like the
+    /// --since/--until dates.
+    #[arg(long, default_value = "false")]
+    weekend_split: bool,
+
+    /// List the short ids of the commits counted for each author
+    #[arg(long, default_value = "false")]
+    with_commits: bool,
+
+    /// Show the median added + deleted lines per commit of each author
+    #[arg(long, default_value = "false")]
+    show_median_commit: bool,
+
+    /// Show first and last commit dates (YYYY-MM-DD) per author
+    #[arg(long, default_value = "false")]
+    show_first_last: bool,
+
+    /// Print the resolved options, repository and ref to stderr before running
+    #[arg(long, default_value = "false")]
+    explain: bool,
+
+    /// Browse the author table in the terminal, with sorting and a date range prompt
+    #[cfg(feature = "tui")]
+    #[arg(long, default_value = "false")]
+    tui: bool,
+
+    /// Print the JSON Schema of --format json and exit
+    #[arg(long, default_value = "false", hide = true)]
+    print_json_schema: bool,
+
+    /// Print the name and versions of git-stats, git2 and libgit2 as JSON and exit
+    #[arg(long, default_value = "false")]
+    version_json: bool,
+
+    /// Print environment and repository diagnostics and exit
+    #[arg(long, default_value = "false")]
+    doctor: bool,
+
+    /// Emit `{"processed":N,"total":M}` progress lines on stderr
+    #[arg(long, default_value = "false")]
+    progress_json: bool,
+
+    /// Suppress warnings and other messages on stderr
+    #[arg(short, long, default_value = "false", conflicts_with = "verbose")]
+    quiet: bool,
+    /// More messages on stderr, repeat for per-commit details
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Output format.
+    ///
+    /// Reports replacing the author table, like --gaps or --timeseries, take
+    /// only text and tsv-no-prose; --by-type takes json and jsonl too.
+    #[arg(long, value_enum, default_value = "text")]
+    format: Format,
+    /// Output columns and their order, e.g. `name,commits,added`, for all table and JSON formats.
+    ///
+    /// Columns that need an option, like `score` with --blame-weight, are 0
+    /// without it.
+    #[arg(long, value_enum, value_name = "FIELDS", value_delimiter = ',')]
+    fields: Vec<output::Field>,
+    /// Abbreviate counts like `1.5M` or `12.3k` in text, markdown, asciidoc and html tables
+    #[arg(long, visible_alias = "compact-numbers", default_value = "false")]
+    human: bool,
+    /// Append the summary sentence as a last column of --format tsv-no-prose
+    #[arg(long, default_value = "false")]
+    verbose_text: bool,
+    /// Add zero rows for the days without commits to --format timeline-csv
+    #[arg(long, default_value = "false")]
+    fill_gaps: bool,
+    /// Indent JSON output of --format json/jsonl
+    #[arg(long, default_value = "false")]
+    json_pretty: bool,
+    /// Link author names to `<BASE>/commits?author=<email>` in html/markdown output
+    #[arg(long, value_name = "BASE")]
+    repo_url: Option<String>,
+    /// Write each author's report to a file of its own in DIR instead of stdout.
+    ///
+    /// Files are named after the author's e-mail, with anything but ASCII
+    /// letters, digits and `-_.@` replaced, and get the --format's extension.
+    /// Only the author table is split: other reports, timeline-csv and
+    /// ndjson-events are rejected.
+    #[arg(long, value_name = "DIR")]
+    split_output: Option<PathBuf>,
+    /// Print the combined report to stdout too with --split-output
+    #[arg(long, default_value = "false", requires = "split_output")]
+    also_combined: bool,
+    /// File to write the report to instead of stdout, required by --format xlsx.
+    ///
+    /// Applies to every report, e.g. --by-type or --timeseries, but not to
+    /// the streamed --format ndjson-events.
+    #[arg(short, long, value_name = "PATH", required_if_eq("format", "xlsx"))]
+    output: Option<PathBuf>,
+    /// Column separator of --format text
+    #[arg(long, value_name = "STR", default_value = "\t")]
+    separator: String,
+    /// Show e-mails in --format shortlog, like `git shortlog -e`
+    #[arg(long, default_value = "false")]
+    with_email: bool,
+
+    /// Output template, e.g. "%n\t%e\t%c\t%a\t%d".
+    ///
+    /// Placeholders: %n name, %e email, %c commits, %a added, %d deleted,
+    /// %m month, %y year, %f first commit, %l last commit, %r add ratio,
+    /// %M module, %% literal percent.
+    #[arg(long, value_parser = parse_template)]
+    template: Option<Template>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum GroupBy {
+    /// Author name
+    #[value(alias = "author")]
+    Name,
+    /// Author E-mail
+    Email,
+    /// Exact `name <email>` pair, rows are labelled with the full identity
+    Identity,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum DateKind {
+    /// Author date, preserved by rebases and amends
+    Author,
+    /// Committer date
+    Commit,
+}
+
+impl DateKind {
+    fn time_of(self, commit: &git2::Commit) -> DateTime<Local> {
+        let time = match self {
+            DateKind::Author => commit.author().when(),
+            DateKind::Commit => commit.time(),
+        };
+        Local.timestamp_opt(time.seconds(), 0).unwrap()
+    }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 enum SortBy {
     /// Author name
     Name,
@@ -56,9 +625,15 @@ enum SortBy {
     Added,
     /// Deleted lines
     Deleted,
+    /// Added lines / (added + deleted)
+    AddRatio,
+    /// Median added + deleted lines per commit
+    MedianCommit,
+    /// Lines weighted by --weight-path
+    Weighted,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 enum Order {
     /// Ascending
     Asc,
@@ -66,6 +641,179 @@ enum Order {
     Desc,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum WalkOrder {
+    /// Parents are never shown before their children
+    Topo,
+    /// Commit time, newest first
+    Date,
+    /// Reverse of the default walk
+    Reverse,
+}
+
+impl WalkOrder {
+    fn sort(self) -> Sort {
+        match self {
+            WalkOrder::Topo => Sort::TOPOLOGICAL,
+            WalkOrder::Date => Sort::TIME,
+            WalkOrder::Reverse => Sort::REVERSE,
+        }
+    }
+}
+
+/// `--weight-path GLOB=WEIGHT`
+#[derive(Clone, Debug)]
+struct PathWeight {
+    glob: String,
+    weight: f64,
+}
+
+/// Parse `GLOB=WEIGHT`, split at the last `=`.
+fn parse_path_weight(s: &str) -> Result<PathWeight, String> {
+    let (glob, weight) = s
+        .rsplit_once('=')
+        .ok_or_else(|| "expected GLOB=WEIGHT".to_string())?;
+    let weight: f64 = weight.parse().map_err(|e| format!("{e}"))?;
+    if !weight.is_finite() || weight < 0.0 {
+        return Err(format!("{weight} is not a non-negative weight"));
+    }
+    Ok(PathWeight {
+        glob: glob.to_string(),
+        weight,
+    })
+}
+
+#[derive(Clone, Debug)]
+struct NameTransform {
+    pattern: Regex,
+    replacement: String,
+}
+
+/// Parse `REGEX=REPLACEMENT`, split at the first `=`.
+fn parse_name_transform(s: &str) -> Result<NameTransform, String> {
+    let (pattern, replacement) = s
+        .split_once('=')
+        .ok_or_else(|| "expected REGEX=REPLACEMENT".to_string())?;
+    let pattern = Regex::new(pattern).map_err(|e| e.to_string())?;
+    Ok(NameTransform {
+        pattern,
+        replacement: replacement.to_string(),
+    })
+}
+
+fn parse_weight(s: &str) -> Result<f64, String> {
+    let weight: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&weight) {
+        Ok(weight)
+    } else {
+        Err(format!("{weight} is not in 0.0-1.0"))
+    }
+}
+
+fn parse_percent(s: &str) -> Result<f64, String> {
+    let percent: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if percent > 0.0 && percent <= 100.0 {
+        Ok(percent)
+    } else {
+        Err(format!("{percent} is not in (0, 100]"))
+    }
+}
+
+/// Whether `oid` falls into a `percent` sample, stable for a given seed.
+fn sampled(oid: Oid, seed: u64, percent: f64) -> bool {
+    // FNV-1a, so the sample doesn't change across toolchains.
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in seed.to_le_bytes().iter().chain(oid.as_bytes()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    ((hash % 10_000) as f64) < percent * 100.0
+}
+
+/// Scale a sampled count up to an estimate of the full value.
+fn scale(n: usize, factor: f64) -> usize {
+    (n as f64 * factor).round() as usize
+}
+
+impl Totals {
+    fn merge(&mut self, other: Totals) {
+        self.commits += other.commits;
+        self.added += other.added;
+        self.deleted += other.deleted;
+    }
+
+    fn scale(&mut self, factor: f64) {
+        self.commits = scale(self.commits, factor);
+        self.added = scale(self.added, factor);
+        self.deleted = scale(self.deleted, factor);
+    }
+}
+
+/// Sort authors, by `--blame-weight` score if `by_score`; ties are broken
+/// by name and e-mail so the order is stable across runs.
+fn sort_users(stats: &mut [User], sort_by: SortBy, order: Order, by_score: bool) {
+    stats.sort_by(|a, b| {
+        let cmp = match sort_by {
+            _ if by_score => a.score.total_cmp(&b.score),
+            SortBy::Name => a.name.cmp(&b.name),
+            SortBy::Email => a.email.cmp(&b.email),
+            SortBy::Commits => a.commits.cmp(&b.commits),
+            SortBy::Added => a.added.cmp(&b.added),
+            SortBy::Deleted => a.deleted.cmp(&b.deleted),
+            SortBy::AddRatio => a.add_ratio().total_cmp(&b.add_ratio()),
+            SortBy::MedianCommit => a.median_commit_lines().total_cmp(&b.median_commit_lines()),
+            SortBy::Weighted => a.weighted_lines.total_cmp(&b.weighted_lines),
+        };
+        match order {
+            Order::Asc => cmp,
+            Order::Desc => cmp.reverse(),
+        }
+        .then_with(|| a.name.cmp(&b.name))
+        .then_with(|| a.email.cmp(&b.email))
+    });
+}
+
+/// Median of `values`, 0 when empty.
+fn median(values: &[usize]) -> f64 {
+    let mut values = values.to_vec();
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    match values.len() {
+        0 => 0.0,
+        n if n.is_multiple_of(2) => (values[mid - 1] + values[mid]) as f64 / 2.0,
+        _ => values[mid] as f64,
+    }
+}
+
+/// Minimum number of top authors whose added lines reach `percent` of the total.
+fn bus_factor(stats: &[User], percent: f64) -> usize {
+    let mut added = stats.iter().map(|u| u.added).collect::<Vec<_>>();
+    added.sort_unstable_by(|a, b| b.cmp(a));
+    let target = added.iter().sum::<usize>() as f64 * percent / 100.0;
+    let mut sum = 0;
+    for (i, lines) in added.iter().enumerate() {
+        sum += lines;
+        if sum as f64 >= target {
+            return i + 1;
+        }
+    }
+    added.len()
+}
+
+/// Added lines as a percentage of all changed lines, 0 without changes.
+fn add_ratio(added: usize, deleted: usize) -> f64 {
+    ratio(added, added + deleted) * 100.0
+}
+
+/// `part / total`, or 0 when `total` is 0.
+fn ratio(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
 fn parse_time(s: &str) -> Result<DateTime<chrono::Local>, String> {
     // 尝试解析日期
     if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
@@ -87,138 +835,2459 @@ fn parse_time(s: &str) -> Result<DateTime<chrono::Local>, String> {
         return Ok(datetime.with_timezone(&chrono::Local));
     }
 
-    Err("Invalid time format".to_string())
+    // 尝试解析 git 风格的相对时间，如 "3 days ago"
+    approxidate::parse(s, Local::now()).ok_or_else(|| "Invalid time format".to_string())
 }
 
-struct User {
-    email: String,
-    time: DateTime<Local>,
-    commits: usize,
-    added: usize,
-    deleted: usize,
+/// Resolve `--exclude-commit` and `--exclude-commits-file` to commit ids.
+fn excluded_commits(repo: &Repository, cli: &Cli) -> Result<HashSet<Oid>, AppError> {
+    let mut specs = cli.exclude_commit.clone();
+    if let Some(path) = cli.exclude_commits_file.as_ref() {
+        let content = std::fs::read_to_string(path)?;
+        specs.extend(
+            content
+                .lines()
+                .map(|line| line.split('#').next().unwrap_or("").trim())
+                .filter(|line| !line.is_empty())
+                .map(String::from),
+        );
+    }
+    let mut excluded = HashSet::new();
+    for spec in specs {
+        let commit = repo
+            .revparse_single(&spec)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|e| {
+                AppError::RefResolve(format!("excluded commit {spec}: {}", e.message()))
+            })?;
+        excluded.insert(commit.id());
+    }
+    Ok(excluded)
 }
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
-    let repo = cli.repository.as_deref().unwrap_or(".");
-    let repo = Repository::open(repo)?;
-    let mut revwalk = repo.revwalk()?;
-    // revwalk.push_glob("")?;
-    revwalk.push_head()?;
-
-    let mailmap = repo.mailmap()?;
-
-    let mut stats: HashMap<String, User> = HashMap::new();
-
-    for oid in revwalk {
-        let oid = oid?;
-        let commit = repo.find_commit(oid)?;
-        let time: DateTime<Local> = Local.timestamp_opt(commit.time().seconds(), 0).unwrap();
 
-        if let Some(since) = cli.since.as_ref() {
-            if time < *since {
-                continue;
-            }
+impl Cli {
+    /// Key under which an identity is aggregated.
+    fn group_key(&self, name: &str, email: &str) -> String {
+        if let Some(orgs) = self.org_map.as_ref() {
+            return orgs.org(email);
         }
-
-        if let Some(un) = cli.until.as_ref() {
-            if time > *un {
-                continue;
-            }
+        match self.group_by.unwrap_or(GroupBy::Name) {
+            GroupBy::Name => name.to_string(),
+            GroupBy::Email => email.to_string(),
+            GroupBy::Identity => format!("{name} <{email}>"),
         }
+    }
 
-        let author = commit.author();
-        let can_au = mailmap.resolve_signature(&author)?;
-        let author_name = can_au.name().unwrap_or("").to_string();
-        let email = can_au.email().unwrap_or("").to_string();
+    /// Apply the `--name-transform` rewrites in order.
+    fn transform_name(&self, name: &str) -> String {
+        let mut name = name.to_string();
+        for transform in &self.name_transform {
+            name = transform
+                .pattern
+                .replace_all(&name, transform.replacement.as_str())
+                .into_owned();
+        }
+        name
+    }
 
-        if !cli.no_bot && author_name.contains("dependabot") {
-            continue;
+    /// Name shown for an aggregated identity.
+    fn label(&self, name: String, email: &str) -> String {
+        if let Some(orgs) = self.org_map.as_ref() {
+            return orgs.org(email);
         }
-        if !cli.no_root && author_name == "root" {
-            continue;
+        match self.group_by {
+            Some(GroupBy::Identity) => format!("{name} <{email}>"),
+            _ => name,
         }
-        if !cli.no_ubuntu && author_name == "ubuntu" {
-            continue;
+    }
+
+    fn log_level(&self) -> logging::Level {
+        match (self.quiet, self.verbose) {
+            (true, _) => logging::Level::Quiet,
+            (false, 0) => logging::Level::Warn,
+            (false, 1) => logging::Level::Info,
+            (false, _) => logging::Level::Debug,
         }
+    }
 
-        let tree = commit.tree()?;
-        let parent_tree = if commit.parents().len() > 0 {
-            Some(commit.parent(0)?.tree()?)
-        } else {
-            None
-        };
+    /// Whether per-commit sizes must be kept for each author.
+    fn needs_commit_sizes(&self) -> bool {
+        self.show_median_commit
+            || self.sort_by == SortBy::MedianCommit
+            || self.fields.contains(&output::Field::MedianCommitLines)
+    }
 
-        let mut diff_opts = DiffOptions::new();
-        for p in &cli.glob {
-            diff_opts.pathspec(p);
-        }
+    /// Whether per-commit times must be kept for each author.
+    fn needs_commit_times(&self) -> bool {
+        self.work_hours || self.gaps
+    }
 
-        let diff =
-            repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_opts))?;
+    /// Whether line counts must be computed per file instead of per diff.
+    fn needs_file_stats(&self) -> bool {
+        self.separate_lockfiles
+            || self.split_tests
+            || !self.exclude_path_regex.is_empty()
+            || !self.weight_path.is_empty()
+            || self.ext_breakdown
+            || self.grep_lines.is_some()
+            || self.by_file_author_matrix.is_some()
+    }
 
-        let diff_status = diff.stats()?;
+    /// Whether a commit time lies within --since/--until, see --half-open.
+    fn in_range(&self, time: DateTime<Local>) -> bool {
+        self.since.is_none_or(|since| time >= since)
+            && self.until.is_none_or(|until| {
+                if self.half_open {
+                    time < until
+                } else {
+                    time <= until
+                }
+            })
+    }
 
-        let insertions = diff_status.insertions();
-        let deletions = diff_status.deletions();
+    /// Whether a message passes `--grep`, any pattern may match.
+    fn matches_grep(&self, message: &str) -> bool {
+        self.grep.is_empty() || self.grep.iter().any(|re| re.is_match(message))
+    }
 
-        if insertions == 0 && deletions == 0 {
-            continue;
-        }
+    /// Whether commits of an author are skipped, see `--no-bot`, `--no-root`
+    /// and `--no-ubuntu`.
+    fn excludes_author(&self, name: &str) -> bool {
+        (!self.no_bot && name.contains("dependabot"))
+            || (!self.no_root && name == "root")
+            || (!self.no_ubuntu && name == "ubuntu")
+    }
 
-        let entry = stats.entry(author_name).or_insert(User {
-            email,
-            time,
-            commits: 0,
-            added: 0,
-            deleted: 0,
-        });
-        entry.time = time;
-        entry.commits += 1; // Increment commit count
-        entry.added += insertions;
-        entry.deleted += deletions;
+    fn excludes_path(&self, path: &std::path::Path) -> bool {
+        let path = path.to_string_lossy();
+        self.exclude_path_regex.iter().any(|re| re.is_match(&path))
     }
+}
 
-    let mut stats = stats.into_iter().collect::<Vec<_>>();
-    stats.sort_by(|a, b| {
-        let cmp = match cli.sort_by {
-            SortBy::Name => a.0.cmp(&b.0),
-            SortBy::Email => a.1.email.cmp(&b.1.email),
-            SortBy::Commits => a.1.commits.cmp(&b.1.commits),
-            SortBy::Added => a.1.added.cmp(&b.1.added),
-            SortBy::Deleted => a.1.deleted.cmp(&b.1.deleted),
-        };
-        match cli.order {
-            Order::Asc => cmp,
-            Order::Desc => cmp.reverse(),
-        }
-    });
+/// Commit message, lossily decoded so legacy encodings still match filters.
+fn message<'c>(commit: &'c git2::Commit) -> Cow<'c, str> {
+    String::from_utf8_lossy(commit.message_bytes())
+}
 
-    for (
-        author,
-        User {
-            email,
-            time,
-            commits,
-            added,
-            deleted,
-        },
-    ) in stats
-    {
-        if added == 0 && deleted == 0 {
+/// Look up an `Author-Override: Name <email>` line in the commit's note.
+fn note_author_override(
+    repo: &Repository,
+    notes_ref: &str,
+    oid: Oid,
+) -> Result<Option<Signature<'static>>, git2::Error> {
+    let notes_ref = if notes_ref.starts_with("refs/") {
+        notes_ref.to_string()
+    } else {
+        format!("refs/notes/{notes_ref}")
+    };
+    let note = match repo.find_note(Some(&notes_ref), oid) {
+        Ok(note) => note,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let Some(message) = note.message() else {
+        return Ok(None);
+    };
+    for line in message.lines() {
+        let Some(value) = line.trim().strip_prefix("Author-Override:") else {
             continue;
-        }
-        if let Some(m) = cli.module.as_ref() {
-            println!(
-            "{m}\t{author}\t{email}\t{commits}\t{added}\t{deleted}\t 从 {} 年 {} 月至今，共提交 commit {commits} 个， 新增代码 {added} 行, 删除代码 {deleted} 行",
-            time.year(), time.month(),
-        );
-        } else {
-            println!(
-            "{author}\t{email}\t{commits}\t{added}\t{deleted}\t 从 {} 年 {} 月至今，共提交 commit {commits} 个， 新增代码 {added} 行, 删除代码 {deleted} 行",
-            time.year(), time.month(),
-        );
-        }
+        };
+        let Some((name, email)) = parse_identity(value) else {
+            continue;
+        };
+        return Signature::now(name, email).map(Some);
     }
+    Ok(None)
+}
 
-    Ok(())
+/// Number of commits folded into a GitHub-style squash merge, if it is one.
+///
+/// Best-effort: such merges list each squashed commit as a `* subject`
+/// bullet in the body, so two or more bullets after the subject line count
+/// as that many commits. Hand-written bullet lists look the same.
+fn squashed_commits(message: &str) -> Option<usize> {
+    let bullets = message
+        .lines()
+        .skip(1)
+        .filter(|line| line.starts_with("* "))
+        .count();
+    (bullets >= 2).then_some(bullets)
+}
+
+/// Subjects of generated merge commits, see `--exclude-auto-merges`.
+static AUTO_MERGE: LazyLock<[Regex; 2]> = LazyLock::new(|| {
+    [
+        Regex::new(r"^Merge branch '[^']+'( of \S+)?( into \S+)?$").unwrap(),
+        Regex::new(r"^Merge pull request #\d+ from \S+$").unwrap(),
+    ]
+});
+
+/// Whether the subject line of a message is a generated merge message.
+fn is_auto_merge(message: &str) -> bool {
+    let subject = message.lines().next().unwrap_or("").trim_end();
+    AUTO_MERGE.iter().any(|re| re.is_match(subject))
+}
+
+/// Whether the subject line of a message is GitHub's pull request merge message.
+fn is_pull_request_merge(message: &str) -> bool {
+    let subject = message.lines().next().unwrap_or("").trim_end();
+    AUTO_MERGE[1].is_match(subject)
+}
+
+/// Split `Name <email>` into its trimmed parts.
+fn parse_identity(value: &str) -> Option<(&str, &str)> {
+    let (name, rest) = value.split_once('<')?;
+    let (email, _) = rest.split_once('>')?;
+    Some((name.trim(), email.trim()))
+}
+
+/// Identities in the `Reviewed-by:` trailers of a commit message.
+fn reviewers(message: &str) -> Vec<Signature<'static>> {
+    let Ok(trailers) = git2::message_trailers_strs(message) else {
+        return Vec::new();
+    };
+    trailers
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case("Reviewed-by"))
+        .filter_map(|(_, value)| parse_identity(value))
+        .filter_map(|(name, email)| Signature::now(name, email).ok())
+        .collect()
+}
+
+/// Whether an e-mail looks like it comes from an unconfigured machine.
+fn is_invalid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return true;
+    };
+    local.is_empty()
+        || email.contains("(none)")
+        || domain.is_empty()
+        || !domain.contains('.')
+        || domain == "localhost"
+        || domain.ends_with(".localdomain")
+        || domain.ends_with(".local")
+}
+
+/// Generated lockfiles counted apart by `--separate-lockfiles`.
+const LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "go.sum",
+    "Gemfile.lock",
+    "composer.lock",
+    "poetry.lock",
+    "Pipfile.lock",
+];
+
+fn is_lockfile(path: &std::path::Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| LOCKFILES.contains(&name))
+}
+
+/// Whether every changed file of the diff is a deletion.
+fn only_deletes_files(diff: &Diff) -> bool {
+    let mut changed = diff.deltas().filter(|d| d.status() != Delta::Unmodified);
+    changed.next().is_some_and(|d| d.status() == Delta::Deleted)
+        && changed.all(|d| d.status() == Delta::Deleted)
+}
+
+/// Diff a commit against its first parent, detecting renames and copies as
+/// asked for.
+fn commit_diff<'r>(
+    repo: &'r Repository,
+    commit: &Commit,
+    cli: &Cli,
+) -> Result<Diff<'r>, git2::Error> {
+    let oid = commit.id();
+    let tree = commit.tree()?;
+    let parent_tree = if commit.parents().len() > 0 {
+        Some(commit.parent(0)?.tree()?)
+    } else {
+        None
+    };
+
+    let mut diff_opts = DiffOptions::new();
+    for p in &cli.glob {
+        diff_opts.pathspec(p);
+    }
+    // Copy sources are looked up among unmodified files too.
+    diff_opts.include_unmodified(cli.detect_copies);
+    // Otherwise a type change is a deletion and an addition.
+    diff_opts.include_typechange(cli.report_type_changes);
+
+    let mut diff =
+        repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_opts))?;
+    let changed = diff
+        .deltas()
+        .filter(|d| d.status() != Delta::Unmodified)
+        .count();
+    let find_similar = (cli.detect_renames || cli.detect_copies)
+        && {
+            let within = changed <= cli.rename_limit;
+            if !within {
+                warn!(
+                    "{oid}: {changed} changed files exceed --rename-limit {}, skipping rename detection",
+                    cli.rename_limit
+                );
+            }
+            within
+        };
+    if find_similar {
+        diff.find_similar(Some(
+            DiffFindOptions::new()
+                .renames(cli.detect_renames)
+                .copies(cli.detect_copies)
+                .copies_from_unmodified(cli.detect_copies)
+                .rename_limit(cli.rename_limit),
+        ))?;
+        if cli.log_renames {
+            for delta in diff.deltas().filter(|d| d.status() == Delta::Renamed) {
+                eprintln!(
+                    "{oid}: {} -> {}",
+                    delta
+                        .old_file()
+                        .path()
+                        .unwrap_or(std::path::Path::new(""))
+                        .display(),
+                    delta
+                        .new_file()
+                        .path()
+                        .unwrap_or(std::path::Path::new(""))
+                        .display(),
+                );
+            }
+        }
+    }
+    Ok(diff)
+}
+
+/// Abbreviated id of a commit, as `git log --oneline` shows it.
+fn short_id(commit: &Commit) -> Result<String, git2::Error> {
+    let id = commit.as_object().short_id()?;
+    Ok(id.as_str().unwrap_or_default().to_string())
+}
+
+/// Line counts of a single file in a diff.
+struct FileStat {
+    path: PathBuf,
+    added: usize,
+    deleted: usize,
+    /// Added lines matching `--grep-lines`
+    matched_added: usize,
+    /// Deleted lines matching `--grep-lines`
+    matched_deleted: usize,
+}
+
+/// Compute per-file line counts; binary and unchanged files count as zero.
+///
+/// With `grep_lines` the content of every added and deleted line is matched
+/// against it as well, which walks all hunks of the patch.
+fn file_stats(diff: &Diff, grep_lines: Option<&Regex>) -> Result<Vec<FileStat>, git2::Error> {
+    let mut files = Vec::with_capacity(diff.deltas().len());
+    for (idx, delta) in diff.deltas().enumerate() {
+        let path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        let mut file = FileStat {
+            path,
+            added: 0,
+            deleted: 0,
+            matched_added: 0,
+            matched_deleted: 0,
+        };
+        if let Some(patch) = Patch::from_diff(diff, idx)? {
+            (_, file.added, file.deleted) = patch.line_stats()?;
+            if let Some(re) = grep_lines {
+                for hunk in 0..patch.num_hunks() {
+                    for line in 0..patch.num_lines_in_hunk(hunk)? {
+                        let line = patch.line_in_hunk(hunk, line)?;
+                        let counter = match line.origin() {
+                            '+' => &mut file.matched_added,
+                            '-' => &mut file.matched_deleted,
+                            _ => continue,
+                        };
+                        if re.is_match(&String::from_utf8_lossy(line.content())) {
+                            *counter += 1;
+                        }
+                    }
+                }
+            }
+        }
+        files.push(file);
+    }
+    Ok(files)
+}
+
+/// Whether a commit changes both binary and text files.
+///
+/// Relies on `DiffDeltaFlags::BINARY`/`NOT_BINARY`, which libgit2 only fills
+/// in once the file contents were loaded, i.e. after computing line stats.
+fn mixes_binary_and_text(diff: &Diff) -> bool {
+    let mut binary = false;
+    let mut text = false;
+    for delta in diff.deltas().filter(|d| d.status() != Delta::Unmodified) {
+        binary |= delta.flags().is_binary();
+        text |= delta.flags().is_not_binary();
+    }
+    binary && text
+}
+
+/// Commit and line counts of a bucket other than an author.
+#[derive(Default)]
+struct Totals {
+    commits: usize,
+    added: usize,
+    deleted: usize,
+}
+
+struct User {
+    name: String,
+    email: String,
+    /// Earliest counted commit time, `None` for e.g. reviewers without commits
+    first: Option<DateTime<Local>>,
+    /// Latest counted commit time
+    last: Option<DateTime<Local>>,
+    commits: usize,
+    added: usize,
+    deleted: usize,
+    /// Short ids of the counted commits, only with `--with-commits`
+    commit_ids: Vec<String>,
+    /// Lockfile lines added, only with `--separate-lockfiles`
+    lockfile_added: usize,
+    /// Lockfile lines deleted, only with `--separate-lockfiles`
+    lockfile_deleted: usize,
+    /// Test lines added, only with `--split-tests`
+    test_added: usize,
+    /// Test lines deleted, only with `--split-tests`
+    test_deleted: usize,
+    /// Non-merge commits with a message below `--min-message-length`
+    short_messages: usize,
+    /// `Reviewed-by:` trailers naming the author, only with `--credit-reviewers`
+    reviews: usize,
+    /// Commits touching more than `--refactor-threshold` files
+    refactor_commits: usize,
+    /// Commits changing both binary and text files, only with `--show-mixed-commits`
+    mixed_commits: usize,
+    /// Commits in the range whatever their paths, only with `--count-all-commits-in-range`
+    all_commits: usize,
+    /// Merged pull requests, only with `--count-prs`
+    prs: usize,
+    /// Commits on Saturdays and Sundays, only with `--weekend-split`
+    weekend_commits: usize,
+    /// Lines added and deleted on Saturdays and Sundays, only with `--weekend-split`
+    weekend_lines: usize,
+    /// Commits on Mondays to Fridays, only with `--weekend-split`
+    weekday_commits: usize,
+    /// Lines added and deleted on Mondays to Fridays, only with `--weekend-split`
+    weekday_lines: usize,
+    /// Lines in HEAD last touched by the author, only with `--blame-weight`
+    surviving: usize,
+    /// Blended churn/ownership score, only with `--blame-weight`
+    score: f64,
+    /// Added + deleted lines times their `--weight-path` weight
+    weighted_lines: f64,
+    /// Added lines matching `--grep-lines`
+    grep_added: usize,
+    /// Deleted lines matching `--grep-lines`
+    grep_deleted: usize,
+    /// Commits per changed file, only with `--top-file-per-author`
+    files: HashMap<String, usize>,
+    /// Lines added and deleted per file extension, only with `--ext-breakdown`
+    extensions: HashMap<String, (usize, usize)>,
+    /// Added plus deleted lines per 1000 lines in HEAD, only with `--per-kloc`
+    churn_per_kloc: f64,
+    /// Every counted commit time, only kept when [`Cli::needs_commit_times`]
+    times: Vec<DateTime<Local>>,
+    /// Added + deleted lines of every counted commit, only kept when
+    /// [`Cli::needs_commit_sizes`]
+    commit_sizes: Vec<usize>,
+}
+
+impl User {
+    fn new(name: String, email: String) -> Self {
+        User {
+            name,
+            email,
+            first: None,
+            last: None,
+            commits: 0,
+            added: 0,
+            deleted: 0,
+            commit_ids: Vec::new(),
+            lockfile_added: 0,
+            lockfile_deleted: 0,
+            test_added: 0,
+            test_deleted: 0,
+            short_messages: 0,
+            reviews: 0,
+            refactor_commits: 0,
+            mixed_commits: 0,
+            all_commits: 0,
+            prs: 0,
+            weekend_commits: 0,
+            weekend_lines: 0,
+            weekday_commits: 0,
+            weekday_lines: 0,
+            surviving: 0,
+            score: 0.0,
+            weighted_lines: 0.0,
+            grep_added: 0,
+            grep_deleted: 0,
+            files: HashMap::new(),
+            extensions: HashMap::new(),
+            churn_per_kloc: 0.0,
+            times: Vec::new(),
+            commit_sizes: Vec::new(),
+        }
+    }
+
+    /// Count a commit, walk order doesn't matter for first/last.
+    fn add_commit(&mut self, time: DateTime<Local>, added: usize, deleted: usize) {
+        self.first = Some(self.first.map_or(time, |first| first.min(time)));
+        self.last = Some(self.last.map_or(time, |last| last.max(time)));
+        self.commits += 1;
+        self.added += added;
+        self.deleted += deleted;
+    }
+
+    /// File changed in the most commits and their number, the first path on ties.
+    fn top_file(&self) -> Option<(&str, usize)> {
+        self.files
+            .iter()
+            .min_by_key(|&(path, commits)| (std::cmp::Reverse(*commits), path))
+            .map(|(path, commits)| (path.as_str(), *commits))
+    }
+
+    /// `ext:+added/-deleted` of the `limit` extensions with the most lines.
+    fn ext_breakdown(&self, limit: usize) -> String {
+        let mut extensions = self.extensions.iter().collect::<Vec<_>>();
+        extensions
+            .sort_by_key(|&(ext, (added, deleted))| (std::cmp::Reverse(added + deleted), ext));
+        extensions
+            .into_iter()
+            .take(limit)
+            .map(|(ext, (added, deleted))| format!("{ext}:+{added}/-{deleted}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Whether no lines at all, no reviews, no pull requests and no commit
+    /// outside the GLOB paths were counted for the author.
+    fn is_empty(&self) -> bool {
+        self.reviews == 0
+            && self.all_commits == 0
+            && self.prs == 0
+            && self.added == 0
+            && self.deleted == 0
+            && self.lockfile_added == 0
+            && self.lockfile_deleted == 0
+            && self.test_added == 0
+            && self.test_deleted == 0
+    }
+
+    /// Test lines as a percentage of test and production lines.
+    fn test_ratio(&self) -> f64 {
+        let test = self.test_added + self.test_deleted;
+        ratio(test, test + self.added + self.deleted) * 100.0
+    }
+
+    /// Typical commit size, robust against a few huge commits.
+    fn median_commit_lines(&self) -> f64 {
+        median(&self.commit_sizes)
+    }
+
+    /// High for feature builders, low for refactorers and cleaners.
+    fn add_ratio(&self) -> f64 {
+        add_ratio(self.added, self.deleted)
+    }
+
+    /// Fold another author's counts into this one.
+    fn merge(&mut self, other: User) {
+        self.first = self.first.into_iter().chain(other.first).min();
+        self.last = self.last.max(other.last);
+        self.commits += other.commits;
+        self.added += other.added;
+        self.deleted += other.deleted;
+        self.commit_ids.extend(other.commit_ids);
+        self.lockfile_added += other.lockfile_added;
+        self.lockfile_deleted += other.lockfile_deleted;
+        self.test_added += other.test_added;
+        self.test_deleted += other.test_deleted;
+        self.short_messages += other.short_messages;
+        self.reviews += other.reviews;
+        self.refactor_commits += other.refactor_commits;
+        self.mixed_commits += other.mixed_commits;
+        self.all_commits += other.all_commits;
+        self.prs += other.prs;
+        self.weekend_commits += other.weekend_commits;
+        self.weekend_lines += other.weekend_lines;
+        self.weekday_commits += other.weekday_commits;
+        self.weekday_lines += other.weekday_lines;
+        self.surviving += other.surviving;
+        self.score += other.score;
+        self.weighted_lines += other.weighted_lines;
+        self.grep_added += other.grep_added;
+        self.grep_deleted += other.grep_deleted;
+        for (path, commits) in other.files {
+            *self.files.entry(path).or_default() += commits;
+        }
+        for (ext, (added, deleted)) in other.extensions {
+            let lines = self.extensions.entry(ext).or_default();
+            lines.0 += added;
+            lines.1 += deleted;
+        }
+        self.churn_per_kloc += other.churn_per_kloc;
+        self.times.extend(other.times);
+        self.commit_sizes.extend(other.commit_sizes);
+    }
+
+    /// Scale sampled counts up to estimates, see `--sample`.
+    fn scale(&mut self, factor: f64) {
+        self.commits = scale(self.commits, factor);
+        self.added = scale(self.added, factor);
+        self.deleted = scale(self.deleted, factor);
+        self.lockfile_added = scale(self.lockfile_added, factor);
+        self.lockfile_deleted = scale(self.lockfile_deleted, factor);
+        self.test_added = scale(self.test_added, factor);
+        self.test_deleted = scale(self.test_deleted, factor);
+        self.short_messages = scale(self.short_messages, factor);
+        self.reviews = scale(self.reviews, factor);
+        self.refactor_commits = scale(self.refactor_commits, factor);
+        self.mixed_commits = scale(self.mixed_commits, factor);
+        self.all_commits = scale(self.all_commits, factor);
+        self.prs = scale(self.prs, factor);
+        self.weekend_commits = scale(self.weekend_commits, factor);
+        self.weekend_lines = scale(self.weekend_lines, factor);
+        self.weekday_commits = scale(self.weekday_commits, factor);
+        self.weekday_lines = scale(self.weekday_lines, factor);
+        self.weighted_lines *= factor;
+        self.grep_added = scale(self.grep_added, factor);
+        self.grep_deleted = scale(self.grep_deleted, factor);
+        for commits in self.files.values_mut() {
+            *commits = scale(*commits, factor);
+        }
+        for (added, deleted) in self.extensions.values_mut() {
+            *added = scale(*added, factor);
+            *deleted = scale(*deleted, factor);
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = match parse_cli() {
+        Ok(cli) => cli,
+        Err(e) if is_time_error(&e) => {
+            let _ = e.print();
+            return AppError::TimeParse(e.to_string()).exit_code();
+        }
+        Err(e) => e.exit(),
+    };
+    logging::set_level(cli.log_level());
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            e.exit_code()
+        }
+    }
+}
+
+/// Parse the command line, with $GIT_STATS_SINCE/$GIT_STATS_UNTIL as fallbacks.
+fn parse_cli() -> Result<Cli, clap::Error> {
+    parse_cli_from(std::env::args_os())
+}
+
+/// [`parse_cli`] of the given arguments, the program name first.
+fn parse_cli_from<I, T>(args: I) -> Result<Cli, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let matches = Cli::command().try_get_matches_from(args)?;
+    // Only an explicit --since/--until conflicts, the environment one is overridden.
+    for (id, bound) in [
+        ("since_first_commit", "since"),
+        ("since_tag", "since"),
+        ("since_last_tag", "since"),
+        ("until_tag", "until"),
+    ] {
+        if matches.value_source(id) == Some(ValueSource::CommandLine)
+            && matches.value_source(bound) == Some(ValueSource::CommandLine)
+        {
+            return Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "the argument '--{}' cannot be used with '--{bound} <DATETIME>'",
+                    id.replace('_', "-")
+                ),
+            ));
+        }
+    }
+    // Reports other than the author table print rows of their own, only
+    // --by-type has a JSON form as well.
+    let format = matches
+        .get_one::<Format>("format")
+        .copied()
+        .unwrap_or(Format::Text);
+    for id in REPORT_ARGS {
+        let supported = match format {
+            Format::Text | Format::TsvNoProse => true,
+            Format::Json | Format::Jsonl => *id == "by_type",
+            _ => false,
+        };
+        if !supported && matches.value_source(id) == Some(ValueSource::CommandLine) {
+            return Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "the argument '--{}' cannot be used with '--format {}'",
+                    id.replace('_', "-"),
+                    format.to_possible_value().unwrap().get_name()
+                ),
+            ));
+        }
+    }
+    // Events are streamed while walking, before there's a report to write.
+    if matches.get_one::<Format>("format") == Some(&Format::NdjsonEvents)
+        && matches.get_one::<PathBuf>("output").is_some()
+    {
+        return Err(Cli::command().error(
+            ErrorKind::ArgumentConflict,
+            "the argument '--output <PATH>' cannot be used with '--format ndjson-events'",
+        ));
+    }
+    // Only the author table is split, see `output::write_split`.
+    if matches.get_one::<PathBuf>("split_output").is_some() {
+        let report = REPORT_ARGS
+            .iter()
+            .find(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+            .map(|id| format!("--{}", id.replace('_', "-")));
+        let format = matches
+            .get_one::<Format>("format")
+            .filter(|f| matches!(f, Format::NdjsonEvents | Format::TimelineCsv))
+            .map(|f| format!("--format {}", f.to_possible_value().unwrap().get_name()));
+        if let Some(other) = report.or(format) {
+            return Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                format!("the argument '--split-output <DIR>' cannot be used with '{other}'"),
+            ));
+        }
+    }
+    if matches
+        .get_many::<String>("repository")
+        .map_or(0, |paths| paths.len())
+        > 1
+    {
+        for id in SINGLE_REPOSITORY_ARGS {
+            if matches.value_source(id) == Some(ValueSource::CommandLine) {
+                return Err(Cli::command().error(
+                    ErrorKind::ArgumentConflict,
+                    format!("--{} needs a single --repository", id.replace('_', "-")),
+                ));
+            }
+        }
+    }
+    Cli::from_arg_matches(&matches)
+}
+
+/// Arguments printing a report of their own instead of the author table.
+const REPORT_ARGS: &[&str] = &[
+    "net_within_range",
+    "cochange",
+    "report_type_changes",
+    "timeseries",
+    "by_file_author_matrix",
+    "by_type",
+    "output_totals_only",
+    "bus_factor",
+    "report_invalid_emails",
+    "gaps",
+    "new_since",
+    "work_hours",
+];
+
+/// Arguments that work on one repository and can't be merged across several.
+const SINGLE_REPOSITORY_ARGS: &[&str] = &[
+    "doctor",
+    "since_first_commit",
+    "since_tag",
+    "since_last_tag",
+    "until_tag",
+    "net_within_range",
+    "annotate_tags",
+    "merge_base",
+    "blame_weight",
+    "per_kloc",
+    "progress_json",
+];
+
+/// Whether clap rejected a `--since`/`--until`/`--new-since` value.
+fn is_time_error(e: &clap::Error) -> bool {
+    e.kind() == ErrorKind::ValueValidation
+        && e.get(ContextKind::InvalidArg).is_some_and(|arg| {
+            let arg = arg.to_string();
+            ["--since", "--until", "--new-since"]
+                .iter()
+                .any(|flag| arg.starts_with(flag))
+        })
+}
+
+/// Commits between two `--progress-json` events.
+const PROGRESS_INTERVAL: usize = 100;
+
+/// Commits between two `--max-runtime` clock checks.
+const RUNTIME_CHECK_INTERVAL: usize = 256;
+
+/// Start of the run, the origin of `--max-runtime`.
+static STARTED: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.parse::<u64>()
+        .map(Duration::from_secs)
+        .map_err(|e| format!("{s}: {e}"))
+}
+
+/// Walk of the analyzed commits.
+fn revwalk<'r>(repo: &'r Repository, cli: &Cli) -> Result<git2::Revwalk<'r>, AppError> {
+    let mut revwalk = repo.revwalk()?;
+    if let Some(order) = cli.walk_order {
+        revwalk.set_sorting(order.sort())?;
+    }
+    // revwalk.push_glob("")?;
+    match cli.end_ref.as_deref() {
+        Some(end) => revwalk.push(resolve_commit(repo, end)?)?,
+        None => revwalk
+            .push_head()
+            .map_err(|e| AppError::RefResolve(format!("HEAD: {}", e.message())))?,
+    }
+    if let Some(start) = cli.start_ref.as_deref() {
+        revwalk.hide(resolve_commit(repo, start)?)?;
+    }
+    Ok(revwalk)
+}
+
+/// Resolve a ref or revision to the commit it points to.
+fn resolve_commit(repo: &Repository, rev: &str) -> Result<Oid, AppError> {
+    repo.revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map(|commit| commit.id())
+        .map_err(|e| AppError::RefResolve(format!("{rev}: {}", e.message())))
+}
+
+/// Stash commits, newest first, read from the `refs/stash` reflog like
+/// `git stash list` does.
+fn stashes(repo: &Repository) -> Result<Vec<Oid>, git2::Error> {
+    match repo.reflog("refs/stash") {
+        Ok(reflog) => Ok(reflog.iter().map(|entry| entry.id_new()).collect()),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Time of the commit `tag` points to.
+///
+/// An annotated tag is an object of its own with a tagger date, which is
+/// peeled away so the range follows the tagged commit.
+fn tag_time(repo: &Repository, tag: &str, date: DateKind) -> Result<DateTime<Local>, AppError> {
+    let commit = repo.find_commit(resolve_commit(repo, tag)?)?;
+    Ok(date.time_of(&commit))
+}
+
+/// Fork point of the analyzed ref from `base`, for `--merge-base`.
+fn fork_point(repo: &Repository, cli: &Cli, base: &str) -> Result<Oid, AppError> {
+    let rev = cli.end_ref.as_deref().unwrap_or("HEAD");
+    let head = resolve_commit(repo, rev)?;
+    let base_oid = resolve_commit(repo, base)?;
+    repo.merge_base(head, base_oid).map_err(|e| {
+        AppError::RefResolve(format!("merge base of {rev} and {base}: {}", e.message()))
+    })
+}
+
+/// Name of the most recent tag reachable from the analyzed ref, like
+/// `git describe --tags --abbrev=0`.
+fn last_tag(repo: &Repository, cli: &Cli) -> Result<String, AppError> {
+    let rev = cli.end_ref.as_deref().unwrap_or("HEAD");
+    let commit = repo.find_object(resolve_commit(repo, rev)?, None)?;
+    commit
+        .describe(DescribeOptions::new().describe_tags())
+        .and_then(|describe| {
+            describe.format(Some(DescribeFormatOptions::new().abbreviated_size(0)))
+        })
+        .map_err(|_| {
+            AppError::RefResolve(format!("last tag of {rev}, no tag is reachable from it"))
+        })
+}
+
+/// Emit a `--progress-json` event on stderr.
+fn progress(processed: usize, total: usize) {
+    eprintln!("{{\"processed\":{processed},\"total\":{total}}}");
+}
+
+fn open_repository(path: &str, cli: &Cli) -> Result<Repository, AppError> {
+    // Both handle linked worktrees, whose HEAD is their own checkout.
+    let repo = if cli.discover {
+        Repository::discover(path)
+    } else {
+        Repository::open(path)
+    }
+    .map_err(|e| {
+        if !cli.discover && e.code() == git2::ErrorCode::NotFound {
+            warn!("use --discover to search parent directories");
+        }
+        AppError::RepoOpen(e)
+    })?;
+    info!("analyzing repository at {}", repo.path().display());
+    if cli.explain {
+        let rev = cli.end_ref.as_deref().unwrap_or("HEAD");
+        eprintln!("repository: {}", repo.path().display());
+        match resolve_commit(&repo, rev) {
+            Ok(oid) => eprintln!("ref: {rev} ({oid})"),
+            Err(e) => eprintln!("ref: {rev} ({e})"),
+        }
+    }
+    Ok(repo)
+}
+
+fn run(mut cli: Cli) -> Result<(), AppError> {
+    LazyLock::force(&STARTED);
+    if cli.print_json_schema {
+        output::print_json_schema()?;
+        return Ok(());
+    }
+    if cli.version_json {
+        output::print_version_json()?;
+        return Ok(());
+    }
+    if cli.explain {
+        eprintln!("{cli:#?}");
+    }
+    if cli.new_since.is_some() {
+        cli.since = None;
+    }
+    if cli.repository.len() > 1 {
+        let paths = cli.repository.clone();
+        let (scan, failed) = scan_all(&paths, &cli)?;
+        report(&cli, None, scan)?;
+        if failed > 0 {
+            return Err(AppError::Other(format!(
+                "{failed} of {} repositories could not be analyzed",
+                paths.len()
+            )));
+        }
+        return Ok(());
+    }
+
+    let repo = open_repository(cli.repository.first().map_or(".", String::as_str), &cli)?;
+    if cli.doctor {
+        doctor::print(&repo)?;
+        return Ok(());
+    }
+    if let Some(tag) = cli.since_tag.as_deref() {
+        cli.since = Some(tag_time(&repo, tag, cli.date)?);
+    }
+    if cli.since_last_tag {
+        let tag = last_tag(&repo, &cli)?;
+        info!("since last tag {tag}");
+        cli.since = Some(tag_time(&repo, &tag, cli.date)?);
+    }
+    if let Some(tag) = cli.until_tag.as_deref() {
+        cli.until = Some(tag_time(&repo, tag, cli.date)?);
+    }
+    if let Some(base) = cli.merge_base.as_deref() {
+        let oid = fork_point(&repo, &cli, base)?;
+        if cli.explain {
+            eprintln!("merge base: {base} ({oid})");
+        }
+        cli.start_ref = Some(oid.to_string());
+    }
+    if cli.since_first_commit {
+        let mut first = None;
+        for oid in self::revwalk(&repo, &cli)? {
+            let time = cli.date.time_of(&repo.find_commit(oid?)?);
+            first = Some(first.map_or(time, |f: DateTime<Local>| f.min(time)));
+        }
+        cli.since = first;
+        info!("since first commit: {first:?}");
+    }
+    let scan = scan(&repo, &cli)?;
+    report(&cli, Some(&repo), scan)
+}
+
+/// Scan several repositories on `--jobs` threads and merge the results.
+///
+/// Results are merged in command line order, not completion order, so the
+/// report doesn't depend on scheduling. Failures are printed and counted,
+/// the run only fails when no repository could be scanned.
+fn scan_all(paths: &[String], cli: &Cli) -> Result<(Scan, usize), AppError> {
+    let jobs = cli
+        .jobs
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(paths.len());
+    let next = AtomicUsize::new(0);
+    let mut results = std::thread::scope(|scope| {
+        let workers = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(idx) else {
+                            break results;
+                        };
+                        let result = open_repository(path, cli).and_then(|repo| scan(&repo, cli));
+                        results.push((idx, result));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("scan thread panicked"))
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|(idx, _)| *idx);
+
+    let mut merged: Option<Scan> = None;
+    let mut first_error = None;
+    let mut failed = 0;
+    for (idx, result) in results {
+        match result {
+            Ok(scan) => match merged.as_mut() {
+                Some(merged) => merged.merge(scan),
+                None => merged = Some(scan),
+            },
+            Err(e) => {
+                eprintln!("error: {}: {e}", paths[idx]);
+                failed += 1;
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    let merged = merged.ok_or_else(|| first_error.unwrap())?;
+    if let Some(max) = cli.max_authors {
+        if merged.stats.len() > max {
+            return Err(AppError::Other(format!(
+                "more than {max} distinct authors, check your mailmap"
+            )));
+        }
+    }
+    Ok((merged, failed))
+}
+
+/// Aggregates of one walk, see [`scan`].
+struct Scan {
+    /// One entry per distinct author, keyed by [`Cli::group_key`]
+    stats: HashMap<String, User>,
+    by_type: HashMap<(String, Option<String>), Totals>,
+    series: HashMap<String, Totals>,
+    /// Totals per day and author key for --format timeline-csv
+    timeline: HashMap<(String, String), Totals>,
+    /// Range bounds for --net-within-range
+    oldest: Option<(DateTime<Local>, Oid)>,
+    newest: Option<(DateTime<Local>, Oid)>,
+    cochange: cochange::CoChange,
+    /// Lines per (file, author key) for --by-file-author-matrix
+    matrix: HashMap<(String, String), usize>,
+    /// Files changing type, for --report-type-changes
+    type_changes: Vec<type_changes::TypeChange>,
+    /// The walk was cut short by --max-runtime
+    partial: bool,
+}
+
+impl Scan {
+    /// Fold the scan of another repository into this one.
+    fn merge(&mut self, other: Scan) {
+        for (key, user) in other.stats {
+            match self.stats.entry(key) {
+                Entry::Occupied(mut entry) => entry.get_mut().merge(user),
+                Entry::Vacant(entry) => {
+                    entry.insert(user);
+                }
+            }
+        }
+        for (key, totals) in other.by_type {
+            self.by_type.entry(key).or_default().merge(totals);
+        }
+        for (key, totals) in other.series {
+            self.series.entry(key).or_default().merge(totals);
+        }
+        for (key, totals) in other.timeline {
+            self.timeline.entry(key).or_default().merge(totals);
+        }
+        self.cochange.merge(other.cochange);
+        self.type_changes.extend(other.type_changes);
+        for (key, lines) in other.matrix {
+            *self.matrix.entry(key).or_default() += lines;
+        }
+        self.partial |= other.partial;
+    }
+}
+
+/// Commits skipped by the walk per reason, shown with `--verbose`.
+#[derive(Default)]
+struct SkipTally(BTreeMap<&'static str, usize>);
+
+impl SkipTally {
+    fn add(&mut self, reason: &'static str) {
+        *self.0.entry(reason).or_default() += 1;
+    }
+
+    /// The result of reading a commit's diff, or `None` after counting the
+    /// commit as skipped for a "diff-error", e.g. for a missing blob.
+    fn ok_or_skip<T>(&mut self, oid: Oid, result: Result<T, git2::Error>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                info!("{oid}: skipping, diff failed: {e}");
+                self.add("diff-error");
+                None
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SkipTally {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let reasons = self
+            .0
+            .iter()
+            .map(|(reason, count)| format!("{reason} {count}"))
+            .collect::<Vec<_>>();
+        f.write_str(&reasons.join(", "))
+    }
+}
+
+/// Entry of `key` in `stats`, created for `name <email>` when it's new.
+///
+/// Every author entry is created here, so that --max-authors bounds them all.
+fn author_entry<'s>(
+    stats: &'s mut HashMap<String, User>,
+    cli: &Cli,
+    key: String,
+    name: &str,
+    email: &str,
+) -> Result<&'s mut User, AppError> {
+    let authors = stats.len();
+    match stats.entry(key) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => {
+            if let Some(max) = cli.max_authors.filter(|&max| authors >= max) {
+                return Err(AppError::Other(format!(
+                    "more than {max} distinct authors, check your mailmap"
+                )));
+            }
+            let user = User::new(cli.label(name.to_string(), email), email.to_string());
+            Ok(entry.insert(user))
+        }
+    }
+}
+
+/// Walk the commits of one repository.
+fn scan(repo: &Repository, cli: &Cli) -> Result<Scan, AppError> {
+    let revwalk = revwalk(repo, cli)?;
+    // Counted with a separate walk, only when progress is reported.
+    let total = if cli.progress_json {
+        let count = self::revwalk(repo, cli)?.count();
+        cli.max_commits.map_or(count, |max| count.min(max))
+    } else {
+        0
+    };
+
+    let mailmap = repo.mailmap()?;
+    let excluded = excluded_commits(repo, cli)?;
+    let test_pathspec = if cli.split_tests {
+        Some(Pathspec::new(&cli.test_pattern)?)
+    } else {
+        None
+    };
+    let weight_pathspecs = cli
+        .weight_path
+        .iter()
+        .map(|w| Ok((Pathspec::new([&w.glob])?, w.weight)))
+        .collect::<Result<Vec<_>, git2::Error>>()?;
+
+    // One entry per distinct author: memory is bounded by the number of
+    // authors, not by the number of commits walked, unless a mode needs
+    // per-commit times.
+    let mut stats: HashMap<String, User> = HashMap::new();
+    let mut by_type: HashMap<(String, Option<String>), Totals> = HashMap::new();
+    let mut series: HashMap<String, Totals> = HashMap::new();
+    let mut timeline: HashMap<(String, String), Totals> = HashMap::new();
+    // Patch ids of the counted commits for --dedup-cherry-picks
+    let mut patch_ids = HashSet::new();
+    // Names per e-mail and e-mails per name for --strict-identity
+    let mut names: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut emails: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+    let mut walked = 0;
+    // Range bounds for --net-within-range
+    let mut oldest: Option<(DateTime<Local>, Oid)> = None;
+    let mut newest: Option<(DateTime<Local>, Oid)> = None;
+    let mut cochange = cochange::CoChange::default();
+    let mut partial = false;
+    let mut skipped = SkipTally::default();
+    let mut type_changes = Vec::new();
+    let mut matrix: HashMap<(String, String), usize> = HashMap::new();
+    for oid in revwalk {
+        if cli.max_commits.is_some_and(|max| walked >= max) {
+            info!("stopped after --max-commits {walked}");
+            break;
+        }
+        if walked % RUNTIME_CHECK_INTERVAL == 0
+            && cli.max_runtime.is_some_and(|max| STARTED.elapsed() > max)
+        {
+            warn!("--max-runtime exceeded after {walked} commits, results are partial");
+            partial = true;
+            break;
+        }
+        let oid = oid?;
+        walked += 1;
+        if cli.progress_json && walked % PROGRESS_INTERVAL == 0 {
+            progress(walked, total);
+        }
+        if excluded.contains(&oid) {
+            debug!("{oid}: excluded");
+            skipped.add("excluded");
+            continue;
+        }
+        if let Some(percent) = cli.sample {
+            if !sampled(oid, cli.seed, percent) {
+                skipped.add("sample");
+                continue;
+            }
+        }
+        let commit = repo.find_commit(oid)?;
+        let time = cli.date.time_of(&commit);
+
+        if !cli.in_range(time) {
+            skipped.add("date");
+            continue;
+        }
+
+        let message = message(&commit);
+        if !cli.matches_grep(&message) {
+            debug!("{oid}: message doesn't match --grep");
+            skipped.add("grep");
+            continue;
+        }
+        // Before --exclude-auto-merges, which skips the merge's own lines only.
+        if cli.count_prs && is_pull_request_merge(&message) {
+            match commit.parent(1) {
+                Ok(head) => {
+                    let author = head.author();
+                    let author = if cli.no_mailmap {
+                        author
+                    } else {
+                        mailmap.resolve_signature(&author)?
+                    };
+                    let name = cli.transform_name(author.name().unwrap_or(""));
+                    let email = author.email().unwrap_or("").to_string();
+                    if !cli.excludes_author(&name) {
+                        let key = cli.group_key(&name, &email);
+                        author_entry(&mut stats, cli, key, &name, &email)?.prs += 1;
+                    }
+                }
+                Err(_) => debug!("{oid}: pull request merge without a second parent"),
+            }
+        }
+        if cli.exclude_auto_merges && is_auto_merge(&message) {
+            debug!("{oid}: generated merge message");
+            skipped.add("merge-policy");
+            continue;
+        }
+
+        if cli.net_within_range {
+            if oldest.is_none_or(|(t, _)| time < t) {
+                oldest = Some((time, oid));
+            }
+            if newest.is_none_or(|(t, _)| time > t) {
+                newest = Some((time, oid));
+            }
+            continue;
+        }
+
+        let author = match cli.use_notes.as_deref() {
+            Some(notes_ref) => note_author_override(repo, notes_ref, oid)?,
+            None => None,
+        }
+        .unwrap_or_else(|| commit.author());
+        let can_au = if cli.no_mailmap {
+            author
+        } else {
+            mailmap.resolve_signature(&author)?
+        };
+        let author_name = cli.transform_name(can_au.name().unwrap_or(""));
+        let email = can_au.email().unwrap_or("").to_string();
+
+        if cli.excludes_author(&author_name) {
+            skipped.add("author-exclude");
+            continue;
+        }
+
+        if cli.count_all_commits_in_range {
+            let key = cli.group_key(&author_name, &email);
+            author_entry(&mut stats, cli, key, &author_name, &email)?.all_commits += 1;
+        }
+
+        let Some(diff) = skipped.ok_or_skip(oid, commit_diff(repo, &commit, cli)) else {
+            continue;
+        };
+
+        if cli.exclude_pure_deletions && only_deletes_files(&diff) {
+            debug!("{oid}: only deletes files");
+            skipped.add("pure-deletion");
+            continue;
+        }
+
+        if let Some([a, b]) = cli.cochange.as_deref() {
+            cochange.add(&diff, a, b);
+            continue;
+        }
+
+        let mut insertions = 0;
+        let mut deletions = 0;
+        let mut lockfile_insertions = 0;
+        let mut lockfile_deletions = 0;
+        let mut test_insertions = 0;
+        let mut test_deletions = 0;
+        let mut weighted_lines = 0.0;
+        let mut extensions: HashMap<String, (usize, usize)> = HashMap::new();
+        let mut grep_added = 0;
+        let mut grep_deleted = 0;
+        // Lines per file for --by-file-author-matrix
+        let mut file_lines = Vec::new();
+        // Per-file counts when a mode needs them, the diff totals otherwise
+        let line_stats = if cli.needs_file_stats() {
+            file_stats(&diff, cli.grep_lines.as_ref()).map(|files| (files, None))
+        } else {
+            diff.stats().map(|totals| (Vec::new(), Some(totals)))
+        };
+        let Some((files, totals)) = skipped.ok_or_skip(oid, line_stats) else {
+            continue;
+        };
+        if let Some(totals) = totals {
+            insertions = totals.insertions();
+            deletions = totals.deletions();
+        } else {
+            for file in files {
+                if cli.excludes_path(&file.path) {
+                    continue;
+                }
+                grep_added += file.matched_added;
+                grep_deleted += file.matched_deleted;
+                if cli.by_file_author_matrix.is_some() && file.added + file.deleted > 0 {
+                    file_lines.push((
+                        file.path.to_string_lossy().into_owned(),
+                        file.added + file.deleted,
+                    ));
+                }
+                if !weight_pathspecs.is_empty() {
+                    let weight = weight_pathspecs
+                        .iter()
+                        .find(|(spec, _)| spec.matches_path(&file.path, PathspecFlags::DEFAULT))
+                        .map_or(1.0, |(_, weight)| *weight);
+                    weighted_lines += (file.added + file.deleted) as f64 * weight;
+                }
+                if cli.ext_breakdown {
+                    let ext = file
+                        .path
+                        .extension()
+                        .map_or(Cow::Borrowed("(none)"), |e| e.to_string_lossy());
+                    let lines = extensions.entry(ext.into_owned()).or_default();
+                    lines.0 += file.added;
+                    lines.1 += file.deleted;
+                }
+                if cli.separate_lockfiles && is_lockfile(&file.path) {
+                    lockfile_insertions += file.added;
+                    lockfile_deletions += file.deleted;
+                } else if test_pathspec
+                    .as_ref()
+                    .is_some_and(|spec| spec.matches_path(&file.path, PathspecFlags::DEFAULT))
+                {
+                    test_insertions += file.added;
+                    test_deletions += file.deleted;
+                } else {
+                    insertions += file.added;
+                    deletions += file.deleted;
+                }
+            }
+        }
+
+        if cli.report_type_changes {
+            let Some(short_id) = skipped.ok_or_skip(oid, short_id(&commit)) else {
+                continue;
+            };
+            // Before the empty diff check, binary-only changes have no lines.
+            for (path, change) in type_changes::detect(&diff) {
+                type_changes.push(type_changes::TypeChange {
+                    commit: short_id.clone(),
+                    author: author_name.clone(),
+                    path,
+                    change,
+                });
+            }
+            continue;
+        }
+
+        if insertions == 0
+            && deletions == 0
+            && lockfile_insertions == 0
+            && lockfile_deletions == 0
+            && test_insertions == 0
+            && test_deletions == 0
+        {
+            skipped.add("empty-diff");
+            continue;
+        }
+
+        if cli.skip_initial
+            && commit.parent_count() == 0
+            && insertions + lockfile_insertions + test_insertions > cli.initial_threshold
+        {
+            debug!("{oid}: initial import");
+            skipped.add("initial");
+            continue;
+        }
+
+        if cli.dedup_cherry_picks {
+            let Some(patch_id) = skipped.ok_or_skip(oid, diff.patchid(None)) else {
+                continue;
+            };
+            if !patch_ids.insert(patch_id) {
+                debug!("{oid}: same patch as an already counted commit");
+                skipped.add("duplicate-patch");
+                continue;
+            }
+        }
+        // Looked up before anything is counted, so a failure skips the commit.
+        let with_commit_id = cli.with_commits.then(|| short_id(&commit)).transpose();
+        let Some(commit_id) = skipped.ok_or_skip(oid, with_commit_id) else {
+            continue;
+        };
+
+        if cli.format == Format::NdjsonEvents {
+            let files = diff
+                .deltas()
+                .filter(|d| d.status() != Delta::Unmodified)
+                .count();
+            output::print_event(
+                &output::CommitEvent {
+                    oid: oid.to_string(),
+                    author: &author_name,
+                    email: &email,
+                    time: time.to_rfc3339(),
+                    added: insertions,
+                    deleted: deletions,
+                    files,
+                },
+                cli.json_pretty,
+            )?;
+        }
+
+        if cli.strict_identity {
+            names
+                .entry(email.clone())
+                .or_default()
+                .insert(author_name.clone());
+            emails
+                .entry(author_name.clone())
+                .or_default()
+                .insert(email.clone());
+        }
+
+        let key = cli.group_key(&author_name, &email);
+
+        for (path, lines) in file_lines {
+            *matrix.entry((path, key.clone())).or_default() += lines;
+        }
+
+        if cli.by_type {
+            // Only cross types with authors when grouping is asked for.
+            let group = (cli.group_by.is_some() || cli.org_map.is_some()).then(|| key.clone());
+            let totals = by_type
+                .entry((by_type::commit_type(&message).to_string(), group))
+                .or_default();
+            totals.commits += 1;
+            totals.added += insertions;
+            totals.deleted += deletions;
+        }
+
+        if let Some(period) = cli.timeseries {
+            let totals = series.entry(period.key(time)).or_default();
+            totals.commits += 1;
+            totals.added += insertions;
+            totals.deleted += deletions;
+        }
+
+        if cli.format == Format::TimelineCsv {
+            let totals = timeline
+                .entry((Period::Day.key(time), key.clone()))
+                .or_default();
+            totals.commits += 1;
+            totals.added += insertions;
+            totals.deleted += deletions;
+        }
+
+        let entry = author_entry(&mut stats, cli, key, &author_name, &email)?;
+        entry.add_commit(time, insertions, deletions);
+        entry.weighted_lines += weighted_lines;
+        entry.grep_added += grep_added;
+        entry.grep_deleted += grep_deleted;
+        for (ext, (added, deleted)) in extensions {
+            let lines = entry.extensions.entry(ext).or_default();
+            lines.0 += added;
+            lines.1 += deleted;
+        }
+        if cli.expand_squash {
+            if let Some(squashed) = squashed_commits(&message) {
+                debug!("{oid}: squash merge of {squashed} commits");
+                entry.commits += squashed - 1;
+            }
+        }
+        entry.lockfile_added += lockfile_insertions;
+        entry.lockfile_deleted += lockfile_deletions;
+        entry.test_added += test_insertions;
+        entry.test_deleted += test_deletions;
+        if let Some(threshold) = cli.refactor_threshold {
+            let files = diff
+                .deltas()
+                .filter(|d| d.status() != Delta::Unmodified)
+                .count();
+            if files > threshold {
+                entry.refactor_commits += 1;
+            }
+        }
+        if let Some(min) = cli.min_message_length {
+            // Merge messages are generated, so they don't say much about hygiene.
+            if commit.parent_count() < 2 && message.trim().chars().count() < min {
+                entry.short_messages += 1;
+            }
+        }
+        if cli.show_mixed_commits && mixes_binary_and_text(&diff) {
+            entry.mixed_commits += 1;
+        }
+        if cli.top_file_per_author {
+            for delta in diff.deltas().filter(|d| d.status() != Delta::Unmodified) {
+                let Some(path) = delta.new_file().path().or_else(|| delta.old_file().path()) else {
+                    continue;
+                };
+                if !cli.excludes_path(path) {
+                    *entry
+                        .files
+                        .entry(path.to_string_lossy().into_owned())
+                        .or_default() += 1;
+                }
+            }
+        }
+        if cli.weekend_split {
+            // Counted apart rather than subtracted from the totals, which
+            // --sample scales separately.
+            if matches!(time.weekday(), Weekday::Sat | Weekday::Sun) {
+                entry.weekend_commits += 1;
+                entry.weekend_lines += insertions + deletions;
+            } else {
+                entry.weekday_commits += 1;
+                entry.weekday_lines += insertions + deletions;
+            }
+        }
+        entry.commit_ids.extend(commit_id);
+        if cli.needs_commit_times() {
+            entry.times.push(time);
+        }
+        if cli.needs_commit_sizes() {
+            entry.commit_sizes.push(insertions + deletions);
+        }
+
+        if cli.credit_reviewers {
+            for reviewer in reviewers(&message) {
+                let reviewer = if cli.no_mailmap {
+                    reviewer
+                } else {
+                    mailmap.resolve_signature(&reviewer)?
+                };
+                let name = cli.transform_name(reviewer.name().unwrap_or(""));
+                let email = reviewer.email().unwrap_or("").to_string();
+                let key = cli.group_key(&name, &email);
+                author_entry(&mut stats, cli, key, &name, &email)?.reviews += 1;
+            }
+        }
+    }
+
+    if cli.include_stash {
+        // Stashes are made by whoever works in this clone.
+        let me = repo.signature().ok();
+        // Filtered like the walked commits, counting towards --max-commits.
+        for oid in stashes(repo)? {
+            if cli.max_commits.is_some_and(|max| walked >= max) {
+                info!("stopped after --max-commits {walked}");
+                break;
+            }
+            walked += 1;
+            if excluded.contains(&oid) {
+                skipped.add("excluded");
+                continue;
+            }
+            if cli
+                .sample
+                .is_some_and(|percent| !sampled(oid, cli.seed, percent))
+            {
+                skipped.add("sample");
+                continue;
+            }
+            let stash = repo.find_commit(oid)?;
+            let time = cli.date.time_of(&stash);
+            if !cli.in_range(time) {
+                skipped.add("date");
+                continue;
+            }
+            if !cli.matches_grep(&message(&stash)) {
+                skipped.add("grep");
+                continue;
+            }
+            let diff_stats = commit_diff(repo, &stash, cli).and_then(|diff| diff.stats());
+            let Some(diff_stats) = skipped.ok_or_skip(oid, diff_stats) else {
+                continue;
+            };
+            if diff_stats.insertions() == 0 && diff_stats.deletions() == 0 {
+                continue;
+            }
+            let who = me.clone().unwrap_or_else(|| stash.author().to_owned());
+            let who = if cli.no_mailmap {
+                who
+            } else {
+                mailmap.resolve_signature(&who)?
+            };
+            let name = cli.transform_name(who.name().unwrap_or(""));
+            let email = who.email().unwrap_or("").to_string();
+            if cli.excludes_author(&name) {
+                skipped.add("author-exclude");
+                continue;
+            }
+            debug!("{oid}: stash counted for {name}");
+            let key = cli.group_key(&name, &email);
+            author_entry(&mut stats, cli, key, &name, &email)?.add_commit(
+                time,
+                diff_stats.insertions(),
+                diff_stats.deletions(),
+            );
+        }
+    }
+
+    if cli.strict_identity {
+        let conflicts = names
+            .iter()
+            .filter(|(_, names)| names.len() > 1)
+            .map(|(email, names)| format!("<{email}> is used by {names:?}"))
+            .chain(
+                emails
+                    .iter()
+                    .filter(|(_, emails)| emails.len() > 1)
+                    .map(|(name, emails)| format!("{name} uses {emails:?}")),
+            )
+            .collect::<Vec<_>>();
+        if !conflicts.is_empty() {
+            return Err(AppError::Other(format!(
+                "ambiguous identities, add them to the mailmap:\n  {}",
+                conflicts.join("\n  ")
+            )));
+        }
+    }
+
+    if cli.progress_json {
+        progress(walked, total);
+    }
+    info!("walked {walked} commits, {} authors", stats.len());
+    if !skipped.0.is_empty() {
+        info!("skipped commits: {skipped}");
+    }
+    if let Some(n) = skipped.0.get("diff-error") {
+        warn!("{n} commits skipped for diff errors, see --verbose");
+    }
+    Ok(Scan {
+        stats,
+        by_type,
+        series,
+        timeline,
+        oldest,
+        newest,
+        cochange,
+        matrix,
+        type_changes,
+        partial,
+    })
+}
+
+/// Print the report selected by the command line.
+///
+/// `repo` is `None` when several repositories were scanned, the modes that
+/// need one are rejected by [`parse_cli`] then.
+fn report(cli: &Cli, repo: Option<&Repository>, scan: Scan) -> Result<(), AppError> {
+    let Scan {
+        mut stats,
+        mut by_type,
+        mut series,
+        mut timeline,
+        oldest,
+        newest,
+        cochange,
+        matrix,
+        type_changes,
+        partial,
+    } = scan;
+    if let (true, Some(repo)) = (cli.net_within_range, repo) {
+        let (Some((_, oldest)), Some((_, newest))) = (oldest, newest) else {
+            return Err(AppError::EmptyResult);
+        };
+        output::to_output(cli, |out| net::write(out, repo, oldest, newest, &cli.glob))?;
+        return Ok(());
+    }
+    if cli.cochange.is_some() {
+        output::to_output(cli, |out| cochange::write(out, &cochange))?;
+        return Ok(());
+    }
+    if cli.report_type_changes {
+        output::to_output(cli, |out| type_changes::write(out, &type_changes))?;
+        return Ok(());
+    }
+    if stats.is_empty() {
+        return Err(AppError::EmptyResult);
+    }
+    if cli.format == Format::NdjsonEvents {
+        // Already streamed by the scan.
+        return Ok(());
+    }
+    if let Some(percent) = cli.sample {
+        let factor = 100.0 / percent;
+        for user in stats.values_mut() {
+            user.scale(factor);
+        }
+        for totals in by_type
+            .values_mut()
+            .chain(series.values_mut())
+            .chain(timeline.values_mut())
+        {
+            totals.scale(factor);
+        }
+        warn!("estimated from a {percent}% sample of commits");
+    }
+
+    if let Some(period) = cli.timeseries {
+        let tags = match repo.filter(|_| cli.annotate_tags) {
+            Some(repo) => timeseries::tags_by_period(repo, period, cli.date)?,
+            None => HashMap::new(),
+        };
+        output::to_output(cli, |out| {
+            timeseries::write(out, series, &tags, cli.annotate_tags)
+        })?;
+        return Ok(());
+    }
+
+    if let Some(layout) = cli.by_file_author_matrix {
+        // Authors sharing a display name share their cells.
+        let mut by_name: HashMap<(String, String), usize> = HashMap::new();
+        for ((file, key), lines) in matrix {
+            *by_name.entry((file, stats[&key].name.clone())).or_default() += lines;
+        }
+        output::to_output(cli, |out| matrix::write(out, by_name, layout))?;
+        return Ok(());
+    }
+
+    if cli.format == Format::TimelineCsv {
+        // Authors sharing a display name share their rows.
+        let mut by_name: HashMap<(String, String), Totals> = HashMap::new();
+        for ((day, key), totals) in timeline {
+            by_name
+                .entry((day, stats[&key].name.clone()))
+                .or_default()
+                .merge(totals);
+        }
+        output::to_output(cli, |out| {
+            timeseries::write_csv(out, by_name, cli.fill_gaps)
+        })?;
+        return Ok(());
+    }
+
+    if cli.by_type {
+        output::to_output(cli, |out| {
+            by_type::write(
+                out,
+                by_type,
+                cli.sort_by,
+                cli.order,
+                cli.format,
+                cli.json_pretty,
+            )
+        })?;
+        return Ok(());
+    }
+
+    if let (Some(weight), Some(repo)) = (cli.blame_weight, repo) {
+        let (surviving, total_surviving) = blame::surviving_lines(repo, &repo.mailmap()?, cli)?;
+        let total_churn: usize = stats.values().map(|u| u.added + u.deleted).sum();
+        for (key, user) in stats.iter_mut() {
+            user.surviving = surviving.get(key).copied().unwrap_or(0);
+            let churn = ratio(user.added + user.deleted, total_churn);
+            let owned = ratio(user.surviving, total_surviving);
+            user.score = (1.0 - weight) * churn + weight * owned;
+        }
+    }
+
+    if let (true, Some(repo)) = (cli.per_kloc, repo) {
+        let lines = size::head_lines(repo, &cli.glob)?;
+        info!("{lines} lines in HEAD");
+        for user in stats.values_mut() {
+            user.churn_per_kloc = ratio(user.added + user.deleted, lines) * 1000.0;
+        }
+    }
+
+    let mut stats = stats.into_values().collect::<Vec<_>>();
+    sort_users(
+        &mut stats,
+        cli.sort_by,
+        cli.order,
+        cli.blame_weight.is_some(),
+    );
+
+    if cli.output_totals_only {
+        let added: usize = stats.iter().map(|u| u.added).sum();
+        let deleted: usize = stats.iter().map(|u| u.deleted).sum();
+        let total = match cli.sort_by {
+            SortBy::Name | SortBy::Email => stats.len().to_string(),
+            SortBy::Commits => stats.iter().map(|u| u.commits).sum::<usize>().to_string(),
+            SortBy::Added => added.to_string(),
+            SortBy::Deleted => deleted.to_string(),
+            SortBy::AddRatio => format!("{:.1}", add_ratio(added, deleted)),
+            SortBy::Weighted => {
+                format!("{:.1}", stats.iter().map(|u| u.weighted_lines).sum::<f64>())
+            }
+            SortBy::MedianCommit => {
+                let sizes = stats
+                    .iter()
+                    .flat_map(|u| u.commit_sizes.iter().copied())
+                    .collect::<Vec<_>>();
+                format!("{:.1}", median(&sizes))
+            }
+        };
+        output::to_output(cli, |out| writeln!(out, "{total}"))?;
+        return Ok(());
+    }
+
+    if cli.bus_factor {
+        let factor = bus_factor(&stats, cli.bus_factor_threshold);
+        output::to_output(cli, |out| writeln!(out, "{factor}"))?;
+        return Ok(());
+    }
+
+    if cli.report_invalid_emails {
+        output::to_output(cli, |out| {
+            for user in stats.iter().filter(|u| is_invalid_email(&u.email)) {
+                writeln!(out, "{}\t{}\t{}", user.name, user.email, user.commits)?;
+            }
+            Ok::<_, std::io::Error>(())
+        })?;
+        return Ok(());
+    }
+
+    if let Some(limit) = cli.limit {
+        let rest = stats.split_off(limit.min(stats.len()));
+        if cli.others && !rest.is_empty() {
+            let count = rest.len();
+            let mut rest = rest.into_iter();
+            let mut others = rest.next().unwrap();
+            rest.for_each(|user| others.merge(user));
+            others.name = format!("(others, {count} authors)");
+            others.email = String::new();
+            stats.push(others);
+        }
+    }
+
+    if cli.gaps {
+        output::to_output(cli, |out| gaps::write(out, &stats))?;
+        return Ok(());
+    }
+
+    if let Some(since) = cli.new_since {
+        output::to_output(cli, |out| newcomers::write(out, &stats, since))?;
+        return Ok(());
+    }
+
+    if cli.work_hours {
+        if cli.work_start >= cli.work_end {
+            warn!(
+                "empty working window {}-{}, every commit counts as off-hours",
+                cli.work_start, cli.work_end
+            );
+        }
+        output::to_output(cli, |out| {
+            work_hours::write(out, &stats, cli.work_start, cli.work_end)
+        })?;
+        return Ok(());
+    }
+
+    #[cfg(feature = "tui")]
+    if cli.tui {
+        let stats = stats.into_iter().filter(|u| !u.is_empty()).collect();
+        return tui::run(stats, cli, |since, until| {
+            let Some(repo) = repo else {
+                return Err(AppError::Other(
+                    "changing the date range needs a single --repository".to_string(),
+                ));
+            };
+            let mut cli = cli.clone();
+            cli.since = since;
+            cli.until = until;
+            let stats = self::scan(repo, &cli)?.stats.into_values();
+            Ok(stats.filter(|u| !u.is_empty()).collect())
+        });
+    }
+
+    output::print(stats, cli, partial)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fixture::Fixture;
+
+    /// 2024-01-01T00:00:00Z
+    const JAN_1: i64 = 1_704_067_200;
+    const DAY: i64 = 86_400;
+
+    /// Authors of a scan of `fixture`, with `args` after the program name.
+    fn scan_with(fixture: &Fixture, args: &[&str]) -> HashMap<String, User> {
+        let cli = Cli::parse_from(std::iter::once("git-stats").chain(args.iter().copied()));
+        scan(&fixture.repo, &cli).unwrap().stats
+    }
+
+    #[test]
+    fn scan_counts_commits_and_lines_per_author() {
+        let fixture = Fixture::new();
+        fixture.commit(
+            "Alice",
+            "alice@example.com",
+            &[("a.txt", "1\n2\n3\n")],
+            JAN_1,
+        );
+        fixture.commit(
+            "Bob",
+            "bob@example.com",
+            &[("a.txt", "1\n2\n"), ("src/b.txt", "x\n")],
+            JAN_1 + DAY,
+        );
+        fixture.commit(
+            "Alice",
+            "alice@example.com",
+            &[("c.txt", "y\n")],
+            JAN_1 + 2 * DAY,
+        );
+
+        let stats = scan_with(&fixture, &[]);
+        assert_eq!(stats.len(), 2);
+        let alice = &stats["Alice"];
+        assert_eq!((alice.commits, alice.added, alice.deleted), (2, 4, 0));
+        assert_eq!(alice.first.unwrap().timestamp(), JAN_1);
+        assert_eq!(alice.last.unwrap().timestamp(), JAN_1 + 2 * DAY);
+        let bob = &stats["Bob"];
+        assert_eq!((bob.commits, bob.added, bob.deleted), (1, 1, 1));
+    }
+
+    #[test]
+    fn since_and_until_bound_the_range() {
+        let fixture = Fixture::new();
+        fixture.commit("Alice", "alice@example.com", &[("a.txt", "1\n")], JAN_1);
+        fixture.commit(
+            "Bob",
+            "bob@example.com",
+            &[("b.txt", "1\n")],
+            JAN_1 + 10 * DAY,
+        );
+        fixture.commit(
+            "Carol",
+            "carol@example.com",
+            &[("c.txt", "1\n")],
+            JAN_1 + 20 * DAY,
+        );
+
+        let stats = scan_with(
+            &fixture,
+            &[
+                "--since",
+                "2024-01-05T00:00:00Z",
+                "--until",
+                "2024-01-15T00:00:00Z",
+            ],
+        );
+        assert_eq!(stats.keys().collect::<Vec<_>>(), ["Bob"]);
+    }
+
+    #[test]
+    fn half_open_excludes_until() {
+        let fixture = Fixture::new();
+        fixture.commit("Alice", "alice@example.com", &[("a.txt", "1\n")], JAN_1);
+        fixture.commit("Bob", "bob@example.com", &[("b.txt", "1\n")], JAN_1 + DAY);
+
+        let range = [
+            "--since",
+            "2024-01-01T00:00:00Z",
+            "--until",
+            "2024-01-02T00:00:00Z",
+        ];
+        let stats = scan_with(&fixture, &range);
+        assert_eq!(stats.len(), 2);
+        let stats = scan_with(&fixture, &[&range[..], &["--half-open"]].concat());
+        assert_eq!(stats.keys().collect::<Vec<_>>(), ["Alice"]);
+    }
+
+    #[test]
+    fn mailmap_merges_identities() {
+        let fixture = Fixture::new();
+        fixture.mailmap("Alice <alice@example.com> <alice@old.example.com>\n");
+        fixture.commit("alice", "alice@old.example.com", &[("a.txt", "1\n")], JAN_1);
+        fixture.commit(
+            "Alice",
+            "alice@example.com",
+            &[("b.txt", "1\n2\n")],
+            JAN_1 + DAY,
+        );
+
+        let stats = scan_with(&fixture, &[]);
+        assert_eq!(stats.len(), 1);
+        let alice = &stats["Alice"];
+        assert_eq!((alice.commits, alice.added), (2, 3));
+        assert_eq!(alice.email, "alice@example.com");
+    }
+
+    #[test]
+    fn no_mailmap_keeps_raw_identities() {
+        let fixture = Fixture::new();
+        fixture.mailmap("Alice <alice@example.com> <alice@old.example.com>\n");
+        fixture.commit("alice", "alice@old.example.com", &[("a.txt", "1\n")], JAN_1);
+        fixture.commit(
+            "Alice",
+            "alice@example.com",
+            &[("b.txt", "1\n2\n")],
+            JAN_1 + DAY,
+        );
+
+        let stats = scan_with(&fixture, &["--no-mailmap"]);
+        assert_eq!(stats["alice"].email, "alice@old.example.com");
+        assert_eq!(stats["Alice"].commits, 1);
+    }
+
+    #[test]
+    fn root_is_skipped_unless_asked_for() {
+        let fixture = Fixture::new();
+        fixture.commit("root", "root@localhost", &[("a.txt", "1\n")], JAN_1);
+        fixture.commit(
+            "Alice",
+            "alice@example.com",
+            &[("b.txt", "1\n")],
+            JAN_1 + DAY,
+        );
+
+        assert!(!scan_with(&fixture, &[]).contains_key("root"));
+        assert_eq!(scan_with(&fixture, &["--no-root"])["root"].commits, 1);
+    }
+
+    #[test]
+    fn glob_limits_counted_lines() {
+        let fixture = Fixture::new();
+        fixture.commit(
+            "Alice",
+            "alice@example.com",
+            &[("src/main.rs", "fn main() {}\n"), ("README.md", "a\nb\n")],
+            JAN_1,
+        );
+        fixture.commit(
+            "Bob",
+            "bob@example.com",
+            &[("README.md", "a\n")],
+            JAN_1 + DAY,
+        );
+
+        let stats = scan_with(&fixture, &["src/*"]);
+        assert_eq!(stats["Alice"].added, 1);
+        // Commits without matching files aren't counted at all.
+        assert!(!stats.contains_key("Bob"));
+    }
+
+    /// Alice's commit, then Bob's whose blob is missing from the odb.
+    fn fixture_with_missing_blob() -> Fixture {
+        let fixture = Fixture::new();
+        fixture.commit("Alice", "alice@example.com", &[("a.txt", "1\n")], JAN_1);
+        fixture.commit(
+            "Bob",
+            "bob@example.com",
+            &[("b.txt", "1\n2\n")],
+            JAN_1 + DAY,
+        );
+        // Drop the blob Bob added, so that only his diff fails to load.
+        let blob = fixture.repo.blob(b"1\n2\n").unwrap().to_string();
+        let objects = fixture.repo.path().join("objects");
+        std::fs::remove_file(objects.join(&blob[..2]).join(&blob[2..])).unwrap();
+        fixture
+    }
+
+    #[test]
+    fn diff_errors_skip_only_the_broken_commit() {
+        let fixture = fixture_with_missing_blob();
+        let stats = scan_with(&fixture, &[]);
+        assert_eq!(stats["Alice"].added, 1);
+        assert!(!stats.contains_key("Bob"));
+    }
+
+    #[test]
+    fn diff_errors_skip_with_dedup_cherry_picks() {
+        let fixture = fixture_with_missing_blob();
+        let stats = scan_with(&fixture, &["--dedup-cherry-picks", "--with-commits"]);
+        assert_eq!(stats["Alice"].added, 1);
+        assert_eq!(stats["Alice"].commit_ids.len(), 1);
+        assert!(!stats.contains_key("Bob"));
+    }
+
+    #[test]
+    fn reviews_leave_first_and_last_commit_alone() {
+        let fixture = Fixture::new();
+        fixture.commit("Dave", "dave@example.com", &[("a.txt", "1\n")], JAN_1);
+        fixture.commit_message(
+            "Alice",
+            "alice@example.com",
+            &[("b.txt", "1\n")],
+            "change b.txt\n\nReviewed-by: Dave <dave@example.com>\n",
+            JAN_1 + 100 * DAY,
+        );
+        fixture.commit_message(
+            "Alice",
+            "alice@example.com",
+            &[("c.txt", "1\n")],
+            "change c.txt\n\nReviewed-by: Erin <erin@example.com>\n",
+            JAN_1 + 101 * DAY,
+        );
+
+        let stats = scan_with(&fixture, &["--credit-reviewers"]);
+        let dave = &stats["Dave"];
+        assert_eq!(dave.reviews, 1);
+        assert_eq!(dave.first.unwrap().timestamp(), JAN_1);
+        assert_eq!(dave.last.unwrap().timestamp(), JAN_1);
+        // Reviewers without commits have no commit dates at all.
+        assert_eq!(stats["Erin"].reviews, 1);
+        assert!(stats["Erin"].first.is_none() && stats["Erin"].last.is_none());
+    }
+
+    #[test]
+    fn pull_requests_count_for_the_branch_author() {
+        let fixture = Fixture::new();
+        let base = fixture.commit("Alice", "alice@example.com", &[("a.txt", "1\n")], JAN_1);
+        let head = fixture.commit("Bob", "bob@example.com", &[("b.txt", "1\n")], JAN_1 + DAY);
+        fixture.merge(
+            "Carol",
+            "carol@example.com",
+            &[base, head],
+            "Merge pull request #1 from bob/feature",
+            JAN_1 + 200 * DAY,
+        );
+
+        let stats = scan_with(&fixture, &["--count-prs"]);
+        assert_eq!(stats["Bob"].prs, 1);
+        // Dated by Bob's own commit, not by the later merge.
+        assert_eq!(stats["Bob"].first.unwrap().timestamp(), JAN_1 + DAY);
+        assert_eq!(stats["Bob"].last.unwrap().timestamp(), JAN_1 + DAY);
+        assert_eq!(stats["Carol"].prs, 0);
+        assert_eq!(stats["Alice"].prs, 0);
+    }
+
+    #[test]
+    fn weekend_split_survives_sample_scaling() {
+        let fixture = Fixture::new();
+        // Monday and Saturday noon UTC, the same days in most time zones.
+        fixture.commit(
+            "Bob",
+            "bob@example.com",
+            &[("a.txt", "1\n")],
+            JAN_1 + DAY / 2,
+        );
+        fixture.commit(
+            "Alice",
+            "alice@example.com",
+            &[("a.txt", "2\n")],
+            JAN_1 + 5 * DAY + DAY / 2,
+        );
+
+        let mut stats = scan_with(
+            &fixture,
+            &["--weekend-split", "--sample", "80", "--seed", "0"],
+        );
+        let alice = stats.get_mut("Alice").unwrap();
+        assert_eq!(alice.weekend_lines, 2);
+        // Scaled as the report does: 1.25 added and deleted lines round to 1
+        // each, but 2.5 weekend lines round to 3.
+        alice.scale(100.0 / 80.0);
+        assert_eq!(alice.added + alice.deleted, 2);
+        assert_eq!(alice.weekend_lines, 3);
+        assert_eq!(alice.weekday_lines, 0);
+        assert_eq!(alice.weekday_commits, 0);
+    }
+
+    #[test]
+    fn output_applies_to_other_reports() {
+        let fixture = Fixture::new();
+        fixture.commit("Alice", "alice@example.com", &[("a.txt", "1\n")], JAN_1);
+        let path = fixture.path().join("timeline.csv");
+        let cli = Cli::parse_from([
+            "git-stats",
+            "--format",
+            "timeline-csv",
+            "--output",
+            path.to_str().unwrap(),
+        ]);
+
+        report(
+            &cli,
+            Some(&fixture.repo),
+            scan(&fixture.repo, &cli).unwrap(),
+        )
+        .unwrap();
+        let csv = std::fs::read_to_string(path).unwrap();
+        assert!(csv.starts_with("date,author,commits,added,deleted\n"));
+        assert!(csv.contains(",Alice,1,1,0"));
+    }
+
+    #[test]
+    fn blame_weight_keys_transformed_names() {
+        let fixture = Fixture::new();
+        fixture.commit("Dave", "dave@example.com", &[("a.txt", "1\n2\n")], JAN_1);
+        let cli = Cli::parse_from([
+            "git-stats",
+            "--name-transform",
+            " *Dave$=D",
+            "--blame-weight",
+            "1",
+        ]);
+
+        let (surviving, total) =
+            blame::surviving_lines(&fixture.repo, &fixture.repo.mailmap().unwrap(), &cli).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(surviving.get("D"), Some(&2));
+    }
+
+    #[test]
+    fn by_type_respects_json_format() {
+        let fixture = Fixture::new();
+        fixture.commit("Alice", "alice@example.com", &[("a.txt", "1\n")], JAN_1);
+        let path = fixture.path().join("types.json");
+        let cli = Cli::parse_from([
+            "git-stats",
+            "--by-type",
+            "--format",
+            "json",
+            "--output",
+            path.to_str().unwrap(),
+        ]);
+
+        report(
+            &cli,
+            Some(&fixture.repo),
+            scan(&fixture.repo, &cli).unwrap(),
+        )
+        .unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "types": [{ "type": "(other)", "commits": 1, "added": 1, "deleted": 0 }]
+            })
+        );
+    }
+
+    #[test]
+    fn max_authors_bounds_reviewers_too() {
+        let fixture = Fixture::new();
+        fixture.commit_message(
+            "Alice",
+            "alice@example.com",
+            &[("a.txt", "1\n")],
+            "change a.txt\n\nReviewed-by: Dave <dave@example.com>\n",
+            JAN_1,
+        );
+
+        let cli = Cli::parse_from(["git-stats", "--max-authors", "1"]);
+        assert!(scan(&fixture.repo, &cli).is_ok());
+        let cli = Cli::parse_from(["git-stats", "--max-authors", "1", "--credit-reviewers"]);
+        assert!(scan(&fixture.repo, &cli).is_err());
+    }
+
+    #[test]
+    fn by_type_breaks_ties_by_type() {
+        let fixture = Fixture::new();
+        for (i, ty) in ["fix", "docs", "feat"].into_iter().enumerate() {
+            let file = format!("{ty}.txt");
+            fixture.commit_message(
+                "Alice",
+                "alice@example.com",
+                &[(&file, "1\n")],
+                &format!("{ty}: change {file}"),
+                JAN_1 + i as i64 * DAY,
+            );
+        }
+        let path = fixture.path().join("types.tsv");
+        let cli = Cli::parse_from(["git-stats", "--by-type", "--output", path.to_str().unwrap()]);
+
+        report(
+            &cli,
+            Some(&fixture.repo),
+            scan(&fixture.repo, &cli).unwrap(),
+        )
+        .unwrap();
+        let tsv = std::fs::read_to_string(path).unwrap();
+        let types = tsv.lines().map(|l| l.split('\t').next().unwrap());
+        assert_eq!(types.collect::<Vec<_>>(), ["docs", "feat", "fix"]);
+    }
+
+    #[test]
+    fn stashes_go_through_the_commit_filters() {
+        let mut fixture = Fixture::new();
+        fixture.commit("Alice", "alice@example.com", &[("a.txt", "1\n")], JAN_1);
+        std::fs::write(fixture.path().join("a.txt"), "1\n2\n").unwrap();
+        let signature = Signature::new(
+            "Alice",
+            "alice@example.com",
+            &git2::Time::new(JAN_1 + 10 * DAY, 0),
+        )
+        .unwrap();
+        fixture.repo.stash_save(&signature, "wip", None).unwrap();
+
+        let commits = |args: &[&str]| -> usize {
+            scan_with(&fixture, args).values().map(|u| u.commits).sum()
+        };
+        assert_eq!(commits(&["--include-stash"]), 2);
+        assert_eq!(commits(&["--include-stash", "--until", "2024-01-05"]), 1);
+        assert_eq!(commits(&["--include-stash", "--max-commits", "1"]), 1);
+    }
+
+    #[test]
+    fn reports_reject_formats_they_cannot_print() {
+        let parse = |args: &[&str]| parse_cli_from(["git-stats"].iter().chain(args)).map(|_| ());
+        assert!(parse(&["--gaps", "--format", "tsv-no-prose"]).is_ok());
+        assert!(parse(&["--gaps", "--format", "json"]).is_err());
+        assert!(parse(&["--work-hours", "--format", "jsonl"]).is_err());
+        assert!(parse(&["--timeseries", "month", "--format", "html"]).is_err());
+        assert!(parse(&["--bus-factor", "--format", "json"]).is_err());
+        assert!(parse(&["--by-type", "--format", "json"]).is_ok());
+        assert!(parse(&["--by-type", "--format", "markdown"]).is_err());
+    }
+
+    #[test]
+    fn split_output_is_only_for_the_author_table() {
+        let parse = |args: &[&str]| {
+            let split = ["git-stats", "--split-output", "out"];
+            parse_cli_from(split.iter().chain(args)).map(|_| ())
+        };
+        assert!(parse(&[]).is_ok());
+        assert!(parse(&["--format", "json"]).is_ok());
+        assert!(parse(&["--gaps"]).is_err());
+        assert!(parse(&["--by-type", "--format", "json"]).is_err());
+        assert!(parse(&["--format", "timeline-csv"]).is_err());
+        assert!(parse(&["--format", "ndjson-events"]).is_err());
+    }
+
+    #[test]
+    fn skip_initial_respects_threshold() {
+        let fixture = Fixture::new();
+        fixture.commit(
+            "Alice",
+            "alice@example.com",
+            &[("a.txt", "1\n2\n3\n")],
+            JAN_1,
+        );
+        fixture.commit("Bob", "bob@example.com", &[("b.txt", "1\n")], JAN_1 + DAY);
+
+        let stats = scan_with(&fixture, &["--skip-initial"]);
+        assert!(!stats.contains_key("Alice"));
+        assert_eq!(stats["Bob"].commits, 1);
+        let stats = scan_with(&fixture, &["--skip-initial", "--initial-threshold", "3"]);
+        assert_eq!(stats["Alice"].added, 3);
+    }
+
+    #[test]
+    fn grep_lines_counts_matching_lines() {
+        let fixture = Fixture::new();
+        fixture.commit(
+            "Alice",
+            "alice@example.com",
+            &[("a.rs", "// TODO one\nfn a() {}\n// FIXME two\n")],
+            JAN_1,
+        );
+        fixture.commit(
+            "Bob",
+            "bob@example.com",
+            &[("a.rs", "fn a() {}\n// FIXME two\n")],
+            JAN_1 + DAY,
+        );
+
+        let stats = scan_with(&fixture, &["--grep-lines", "TODO|FIXME"]);
+        let alice = &stats["Alice"];
+        assert_eq!((alice.grep_added, alice.grep_deleted), (2, 0));
+        let bob = &stats["Bob"];
+        assert_eq!((bob.grep_added, bob.grep_deleted), (0, 1));
+    }
+
+    #[test]
+    fn grep_matches_invalid_utf8_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let tree = repo.treebuilder(None).unwrap().write().unwrap();
+        let raw = format!(
+            "tree {tree}\n\
+             author A <a@example.com> 0 +0000\n\
+             committer A <a@example.com> 0 +0000\n\n"
+        );
+        let mut raw = raw.into_bytes();
+        raw.extend_from_slice(b"fix: caf\xe9 menu bug\n");
+        let oid = repo
+            .odb()
+            .unwrap()
+            .write(git2::ObjectType::Commit, &raw)
+            .unwrap();
+        let commit = repo.find_commit(oid).unwrap();
+        assert!(commit.message().is_none());
+
+        let cli = Cli::parse_from(["git-stats", "--grep", "menu bug"]);
+        assert!(cli.matches_grep(&message(&commit)));
+        let cli = Cli::parse_from(["git-stats", "--grep", "feature"]);
+        assert!(!cli.matches_grep(&message(&commit)));
+    }
+
+    #[test]
+    fn tag_time_peels_annotated_tag() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let tree = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree).unwrap();
+        let author =
+            Signature::new("A", "a@example.com", &git2::Time::new(1_700_000_000, 0)).unwrap();
+        let oid = repo
+            .commit(Some("HEAD"), &author, &author, "init", &tree, &[])
+            .unwrap();
+        let tagger =
+            Signature::new("T", "t@example.com", &git2::Time::new(1_800_000_000, 0)).unwrap();
+        let object = repo.find_object(oid, None).unwrap();
+        repo.tag("v1.0", &object, &tagger, "release", false)
+            .unwrap();
+
+        let time = tag_time(&repo, "v1.0", DateKind::Commit).unwrap();
+        assert_eq!(time.timestamp(), 1_700_000_000);
+        let time = tag_time(&repo, "refs/tags/v1.0", DateKind::Author).unwrap();
+        assert_eq!(time.timestamp(), 1_700_000_000);
+    }
 }
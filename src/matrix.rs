@@ -0,0 +1,50 @@
+use clap::ValueEnum;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+
+use crate::output::csv_field;
+
+/// Layout of `--by-file-author-matrix`.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum Layout {
+    /// One `file,author,lines` row per file and author who changed it
+    Long,
+    /// One row per file and one column per author, 0 where they didn't change it
+    Wide,
+}
+
+/// Write lines changed (added + deleted) keyed by (file, author) as CSV,
+/// files and authors sorted by name.
+pub fn write(
+    out: &mut dyn Write,
+    matrix: HashMap<(String, String), usize>,
+    layout: Layout,
+) -> io::Result<()> {
+    let matrix = matrix.into_iter().collect::<BTreeMap<_, _>>();
+    match layout {
+        Layout::Long => {
+            writeln!(out, "file,author,lines")?;
+            for ((file, author), lines) in matrix {
+                writeln!(out, "{},{},{lines}", csv_field(&file), csv_field(&author))?;
+            }
+        }
+        Layout::Wide => {
+            let authors = matrix.keys().map(|(_, a)| a).collect::<BTreeSet<_>>();
+            let header = authors.iter().map(|a| csv_field(a)).collect::<Vec<_>>();
+            writeln!(out, "file,{}", header.join(","))?;
+            let files = matrix.keys().map(|(f, _)| f).collect::<BTreeSet<_>>();
+            for file in files {
+                let cells = authors
+                    .iter()
+                    .map(|&author| {
+                        let key = (file.clone(), author.clone());
+                        matrix.get(&key).copied().unwrap_or(0).to_string()
+                    })
+                    .collect::<Vec<_>>();
+                writeln!(out, "{},{}", csv_field(file), cells.join(","))?;
+            }
+        }
+    }
+    Ok(())
+}
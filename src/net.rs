@@ -0,0 +1,43 @@
+use git2::{DiffOptions, Oid, Repository};
+
+use std::io::Write;
+
+use crate::error::AppError;
+
+/// Write the net change between the parent of `oldest` and `newest`.
+///
+/// Work added and reverted inside the range cancels out, but the result
+/// can't be attributed to authors.
+pub fn write(
+    out: &mut dyn Write,
+    repo: &Repository,
+    oldest: Oid,
+    newest: Oid,
+    globs: &[String],
+) -> Result<(), AppError> {
+    let oldest = repo.find_commit(oldest)?;
+    let base = match oldest.parents().next() {
+        Some(parent) => Some(parent.tree()?),
+        None => None,
+    };
+    let tip = repo.find_commit(newest)?.tree()?;
+
+    let mut diff_opts = DiffOptions::new();
+    for p in globs {
+        diff_opts.pathspec(p);
+    }
+    let diff = repo.diff_tree_to_tree(base.as_ref(), Some(&tip), Some(&mut diff_opts))?;
+    let stats = diff.stats()?;
+    let base = match oldest.parent_id(0) {
+        Ok(id) => id.to_string(),
+        Err(_) => "(root)".to_string(),
+    };
+    writeln!(
+        out,
+        "{base}..{newest}\t{}\t{}\t{}",
+        stats.files_changed(),
+        stats.insertions(),
+        stats.deletions()
+    )?;
+    Ok(())
+}
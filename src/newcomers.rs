@@ -0,0 +1,26 @@
+use chrono::{DateTime, Local};
+
+use std::io::{self, Write};
+
+use crate::{output, User};
+
+/// Write the authors whose first commit is at or after `since`, oldest
+/// newcomer first, with their commits so far.
+pub fn write(out: &mut dyn Write, stats: &[User], since: DateTime<Local>) -> io::Result<()> {
+    let mut newcomers = stats
+        .iter()
+        .filter(|u| u.first.is_some_and(|first| first >= since))
+        .collect::<Vec<_>>();
+    newcomers.sort_by_key(|u| u.first);
+    for user in newcomers {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            user.name,
+            user.email,
+            output::day(user.first),
+            user.commits,
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+/// Organizations of e-mails and domains, read from `--org-map`.
+///
+/// One `KEY,ORG` pair per line, where KEY is an e-mail address
+/// (`alice@gmail.com`) or a domain (`example.com` or `@example.com`); keys
+/// are case-insensitive. Blank lines and `#` comments are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct OrgMap {
+    emails: HashMap<String, String>,
+    domains: HashMap<String, String>,
+}
+
+/// Organization of identities without a domain that no entry maps.
+pub const UNKNOWN: &str = "(unknown)";
+
+impl OrgMap {
+    /// Load a mapping file, see [`OrgMap`].
+    pub fn load(path: &str) -> Result<OrgMap, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        OrgMap::parse(&content).map_err(|e| format!("{path}: {e}"))
+    }
+
+    fn parse(content: &str) -> Result<OrgMap, String> {
+        let mut map = OrgMap::default();
+        for (n, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, org)) = line.split_once(',') else {
+                return Err(format!("line {}: expected KEY,ORG", n + 1));
+            };
+            let (key, org) = (key.trim().to_lowercase(), org.trim().to_string());
+            if key.is_empty() || org.is_empty() {
+                return Err(format!("line {}: expected KEY,ORG", n + 1));
+            }
+            match key.strip_prefix('@') {
+                Some(domain) => map.domains.insert(domain.to_string(), org),
+                None if key.contains('@') => map.emails.insert(key, org),
+                None => map.domains.insert(key, org),
+            };
+        }
+        Ok(map)
+    }
+
+    /// Organization of an e-mail: an exact e-mail entry wins over its domain's,
+    /// unmapped e-mails fall back to their domain, or [`UNKNOWN`] without one.
+    pub fn org(&self, email: &str) -> String {
+        let email = email.to_lowercase();
+        if let Some(org) = self.emails.get(&email) {
+            return org.clone();
+        }
+        match email.rsplit_once('@') {
+            Some((_, domain)) if !domain.is_empty() => self
+                .domains
+                .get(domain)
+                .cloned()
+                .unwrap_or_else(|| domain.to_string()),
+            _ => UNKNOWN.to_string(),
+        }
+    }
+}
@@ -0,0 +1,1159 @@
+use chrono::{DateTime, Datelike, Local};
+use clap::ValueEnum;
+use schemars::{json_schema, schema_for, JsonSchema, Schema, SchemaGenerator};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::{Cli, User};
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum Format {
+    /// Tab separated columns followed by a summary sentence
+    Text,
+    /// Tab separated columns only, without the summary and the --module column
+    TsvNoProse,
+    /// `git shortlog -sn` compatible, sorted by commits
+    Shortlog,
+    /// A single JSON document with an `authors` array
+    Json,
+    /// One JSON object per author and line
+    Jsonl,
+    /// Markdown table
+    Markdown,
+    /// AsciiDoc table
+    Asciidoc,
+    /// HTML table
+    Html,
+    /// One JSON object per counted commit instead of per author, streamed
+    NdjsonEvents,
+    /// `date,author,commits,added,deleted` CSV rows per day, for plotting
+    TimelineCsv,
+    /// InfluxDB line protocol, one `git_stats` point per author
+    Influx,
+    /// Prometheus text exposition, e.g. for the node_exporter textfile collector
+    Prometheus,
+    /// bincode 1 encoded `Vec<BinaryRow>`, see [`BinaryRow`]
+    #[cfg(feature = "bincode")]
+    Bincode,
+    /// Excel workbook written to --output, with a totals row
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+    /// The `--format json` document as YAML
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl Format {
+    /// File extension of `--split-output` files.
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Text | Format::Shortlog => "txt",
+            Format::TsvNoProse => "tsv",
+            Format::Json => "json",
+            Format::Jsonl | Format::NdjsonEvents => "jsonl",
+            Format::Markdown => "md",
+            Format::Asciidoc => "adoc",
+            Format::Html => "html",
+            Format::TimelineCsv => "csv",
+            Format::Influx => "lp",
+            Format::Prometheus => "prom",
+            #[cfg(feature = "bincode")]
+            Format::Bincode => "bin",
+            #[cfg(feature = "xlsx")]
+            Format::Xlsx => "xlsx",
+            #[cfg(feature = "yaml")]
+            Format::Yaml => "yaml",
+        }
+    }
+}
+
+/// Column selectable with `--fields`, named like the JSON keys.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+#[value(rename_all = "snake_case")]
+pub enum Field {
+    Module,
+    Name,
+    Email,
+    Commits,
+    Added,
+    Deleted,
+    AddRatio,
+    MedianCommitLines,
+    FirstCommit,
+    LastCommit,
+    LockfileAdded,
+    LockfileDeleted,
+    TestAdded,
+    TestDeleted,
+    TestRatio,
+    ShortMessages,
+    Reviews,
+    RefactorCommits,
+    MixedCommits,
+    AllCommits,
+    Prs,
+    WeekendCommits,
+    WeekdayCommits,
+    WeekendLines,
+    WeekdayLines,
+    ChurnPerKloc,
+    WeightedLines,
+    TopFile,
+    TopFileCommits,
+    GrepAdded,
+    GrepDeleted,
+    Extensions,
+    Surviving,
+    Score,
+}
+
+/// Author record of `--format bincode`.
+///
+/// Encoded with bincode 1's default options as a `Vec<BinaryRow>`: a u64
+/// little-endian element count, then for each row the fields in order.
+/// Strings are a u64 little-endian byte length followed by UTF-8 bytes,
+/// integers are fixed-width little-endian, commit times are Unix seconds.
+#[cfg(feature = "bincode")]
+#[derive(Serialize)]
+pub struct BinaryRow<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    pub commits: u64,
+    pub added: u64,
+    pub deleted: u64,
+    pub first_commit: i64,
+    pub last_commit: i64,
+}
+
+/// JSON document of `--format json`.
+#[derive(Serialize, JsonSchema)]
+struct Report<'a> {
+    query: Query,
+    /// Counts are scaled up from a `--sample`
+    estimated: bool,
+    /// The walk was stopped by `--max-runtime`, later commits are missing
+    partial: bool,
+    authors: Vec<Row<'a>>,
+}
+
+impl<'a> Report<'a> {
+    fn new(stats: &'a [User], cli: &'a Cli, partial: bool) -> serde_json::Result<Self> {
+        Ok(Report {
+            query: Query::new(cli),
+            estimated: cli.sample.is_some(),
+            partial,
+            authors: stats
+                .iter()
+                .map(|user| Row::new(user, cli))
+                .collect::<serde_json::Result<_>>()?,
+        })
+    }
+}
+
+/// Effective query of a report.
+#[derive(Serialize, JsonSchema)]
+struct Query {
+    since: Option<String>,
+    until: Option<String>,
+}
+
+impl Query {
+    fn new(cli: &Cli) -> Self {
+        Query {
+            since: cli.since.map(|t| t.to_rfc3339()),
+            until: cli.until.map(|t| t.to_rfc3339()),
+        }
+    }
+}
+
+/// Serialized author, fields keep this order in the output.
+#[derive(Serialize, JsonSchema)]
+struct AuthorRow<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<&'a str>,
+    name: &'a str,
+    email: &'a str,
+    commits: usize,
+    added: usize,
+    deleted: usize,
+    add_ratio: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    median_commit_lines: Option<f64>,
+    /// `YYYY-MM-DD`, empty for authors without counted commits, e.g. reviewers
+    first_commit: String,
+    last_commit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commits_list: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lockfile_added: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lockfile_deleted: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    test_added: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    test_deleted: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    test_ratio: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    short_messages: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reviews: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refactor_commits: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mixed_commits: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    all_commits: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prs: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weekend_commits: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weekday_commits: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weekend_lines: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weekday_lines: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    churn_per_kloc: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weighted_lines: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_file: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_file_commits: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grep_added: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grep_deleted: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extensions: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    surviving: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f64>,
+}
+
+impl<'a> AuthorRow<'a> {
+    fn new(user: &'a User, cli: &'a Cli) -> Self {
+        // Everything is filled in for --fields to pick from, see `Row`.
+        let all = !cli.fields.is_empty();
+        let lockfiles = all || cli.separate_lockfiles;
+        let blame = all || cli.blame_weight.is_some();
+        let tests = all || cli.split_tests;
+        let weekend = all || cli.weekend_split;
+        let grep_lines = all || cli.grep_lines.is_some();
+        let top_file = all || cli.top_file_per_author;
+        AuthorRow {
+            module: cli.module.as_deref().or(all.then_some("")),
+            name: &user.name,
+            email: &user.email,
+            commits: user.commits,
+            added: user.added,
+            deleted: user.deleted,
+            add_ratio: user.add_ratio(),
+            median_commit_lines: (all || cli.show_median_commit)
+                .then(|| user.median_commit_lines()),
+            first_commit: day(user.first),
+            last_commit: day(user.last),
+            commits_list: cli.with_commits.then_some(user.commit_ids.as_slice()),
+            lockfile_added: lockfiles.then_some(user.lockfile_added),
+            lockfile_deleted: lockfiles.then_some(user.lockfile_deleted),
+            test_added: tests.then_some(user.test_added),
+            test_deleted: tests.then_some(user.test_deleted),
+            test_ratio: tests.then(|| user.test_ratio()),
+            short_messages: (all || cli.min_message_length.is_some())
+                .then_some(user.short_messages),
+            reviews: (all || cli.credit_reviewers).then_some(user.reviews),
+            refactor_commits: (all || cli.refactor_threshold.is_some())
+                .then_some(user.refactor_commits),
+            mixed_commits: (all || cli.show_mixed_commits).then_some(user.mixed_commits),
+            all_commits: (all || cli.count_all_commits_in_range).then_some(user.all_commits),
+            prs: (all || cli.count_prs).then_some(user.prs),
+            weekend_commits: weekend.then_some(user.weekend_commits),
+            weekday_commits: weekend.then_some(user.weekday_commits),
+            weekend_lines: weekend.then_some(user.weekend_lines),
+            weekday_lines: weekend.then_some(user.weekday_lines),
+            churn_per_kloc: (all || cli.per_kloc).then_some(user.churn_per_kloc),
+            weighted_lines: (all || !cli.weight_path.is_empty()).then_some(user.weighted_lines),
+            top_file: top_file.then(|| user.top_file().map_or("", |(path, _)| path)),
+            top_file_commits: top_file.then(|| user.top_file().map_or(0, |(_, n)| n)),
+            grep_added: grep_lines.then_some(user.grep_added),
+            grep_deleted: grep_lines.then_some(user.grep_deleted),
+            extensions: (all || cli.ext_breakdown)
+                .then(|| user.ext_breakdown(cli.ext_breakdown_limit)),
+            surviving: blame.then_some(user.surviving),
+            score: blame.then_some(user.score),
+        }
+    }
+}
+
+/// Serialized author, projected to `--fields` when given.
+#[derive(Serialize, JsonSchema)]
+#[serde(untagged)]
+enum Row<'a> {
+    Full(Box<AuthorRow<'a>>),
+    Fields(Projection),
+}
+
+impl<'a> Row<'a> {
+    fn new(user: &'a User, cli: &'a Cli) -> serde_json::Result<Self> {
+        let row = AuthorRow::new(user, cli);
+        if cli.fields.is_empty() {
+            return Ok(Row::Full(Box::new(row)));
+        }
+        let serde_json::Value::Object(mut row) = serde_json::to_value(row)? else {
+            unreachable!("AuthorRow serializes to an object");
+        };
+        let fields = cli
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let name = field.to_possible_value()?.get_name().to_string();
+                let value = row.remove(&name)?;
+                Some((name, value))
+            })
+            .collect();
+        Ok(Row::Fields(Projection(fields)))
+    }
+}
+
+/// JSON object keeping the `--fields` order.
+struct Projection(Vec<(String, serde_json::Value)>);
+
+impl JsonSchema for Projection {
+    fn schema_name() -> Cow<'static, str> {
+        "Projection".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "object",
+            "description": "The author fields selected with --fields, in that order"
+        })
+    }
+}
+
+impl Serialize for Projection {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Print the author rows in the format selected by `--format`.
+///
+/// `partial` marks a walk cut short by `--max-runtime` in the formats that can say so.
+///
+/// With `--split-output` each author is written to a file of their own
+/// instead, and to stdout as well with `--also-combined`.
+pub fn print(stats: Vec<User>, cli: &Cli, partial: bool) -> io::Result<()> {
+    let stats = stats
+        .into_iter()
+        .filter(|user| !user.is_empty())
+        .collect::<Vec<_>>();
+    if let Some(dir) = cli.split_output.as_deref() {
+        write_split(&stats, cli, dir, partial)?;
+        if !cli.also_combined {
+            return Ok(());
+        }
+    }
+    #[cfg(feature = "xlsx")]
+    if cli.format == Format::Xlsx {
+        // Guaranteed by clap, see `Cli::output`.
+        let path = cli
+            .output
+            .as_deref()
+            .expect("--format xlsx requires --output");
+        return write_xlsx(&stats, cli, path).map_err(io::Error::other);
+    }
+    to_output(cli, |out| write(out, &stats, cli, partial))
+}
+
+/// Run `write` on the `--output` file, or on stdout without one.
+///
+/// Shared by every report, not only the author table.
+pub fn to_output<E: From<io::Error>>(
+    cli: &Cli,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), E>,
+) -> Result<(), E> {
+    if let Some(path) = cli.output.as_deref() {
+        // Renamed into place, so readers like the node_exporter textfile
+        // collector never see a partial file.
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut out = io::BufWriter::new(fs::File::create(&tmp)?);
+        write(&mut out)?;
+        out.flush()?;
+        return Ok(fs::rename(tmp, path)?);
+    }
+    let mut out = io::stdout().lock();
+    write(&mut out)?;
+    Ok(out.flush()?)
+}
+
+/// Write one file per author into `dir`, named by [`file_stem`].
+fn write_split(stats: &[User], cli: &Cli, dir: &Path, partial: bool) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut taken = HashSet::new();
+    for user in stats {
+        let stem = file_stem(user);
+        let mut name = format!("{stem}.{}", cli.format.extension());
+        for n in 2.. {
+            if taken.insert(name.clone()) {
+                break;
+            }
+            name = format!("{stem}-{n}.{}", cli.format.extension());
+        }
+        let path = dir.join(name);
+        let user = std::slice::from_ref(user);
+        #[cfg(feature = "xlsx")]
+        if cli.format == Format::Xlsx {
+            write_xlsx(user, cli, &path).map_err(io::Error::other)?;
+            continue;
+        }
+        let mut out = io::BufWriter::new(fs::File::create(&path)?);
+        write(&mut out, user, cli, partial)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+/// File name of an author's `--split-output` file, without extension.
+///
+/// Made from the e-mail, or the name without one, keeping only ASCII
+/// letters, digits and `-_.@`, so it can't leave the directory.
+fn file_stem(user: &User) -> String {
+    let identity = if user.email.is_empty() {
+        &user.name
+    } else {
+        &user.email
+    };
+    let stem = identity
+        .chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '-' | '_' | '.' | '@' => c,
+            _ => '_',
+        })
+        .take(100)
+        .collect::<String>();
+    let stem = stem.trim_start_matches('.');
+    if stem.is_empty() {
+        "author".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+fn write(out: &mut dyn Write, stats: &[User], cli: &Cli, partial: bool) -> io::Result<()> {
+    match cli.format {
+        Format::Text => write_text(out, stats, cli)?,
+        Format::TsvNoProse => write_tsv(out, stats, cli)?,
+        Format::Shortlog => write_shortlog(out, stats, cli.with_email)?,
+        Format::Markdown => write_markdown(out, stats, cli)?,
+        Format::Asciidoc => write_asciidoc(out, stats, cli)?,
+        Format::Html => write_html(out, stats, cli)?,
+        Format::Influx => write_influx(out, stats, cli)?,
+        Format::Prometheus => write_prometheus(out, stats, cli)?,
+        // Streamed while walking, see `print_event`.
+        Format::NdjsonEvents => {}
+        // Printed from the per-day totals, see `timeseries::write_csv`.
+        Format::TimelineCsv => {}
+        Format::Json => {
+            write_json(out, &Report::new(stats, cli, partial)?, cli.json_pretty)?;
+            writeln!(out)?;
+        }
+        #[cfg(feature = "yaml")]
+        Format::Yaml => {
+            serde_yaml::to_writer(out, &Report::new(stats, cli, partial)?)
+                .map_err(io::Error::other)?;
+        }
+        Format::Jsonl => {
+            for user in stats {
+                write_json(out, &Row::new(user, cli)?, cli.json_pretty)?;
+                writeln!(out)?;
+            }
+        }
+        #[cfg(feature = "bincode")]
+        Format::Bincode => {
+            let rows = stats
+                .iter()
+                .map(|user| BinaryRow {
+                    name: &user.name,
+                    email: &user.email,
+                    commits: user.commits as u64,
+                    added: user.added as u64,
+                    deleted: user.deleted as u64,
+                    first_commit: user.first.map_or(0, |t| t.timestamp()),
+                    last_commit: user.last.map_or(0, |t| t.timestamp()),
+                })
+                .collect::<Vec<_>>();
+            bincode::serialize_into(out, &rows).map_err(io::Error::other)?;
+        }
+        #[cfg(feature = "xlsx")]
+        Format::Xlsx => unreachable!("written to a file by write_xlsx"),
+    }
+    Ok(())
+}
+
+/// Write the author table as a worksheet with a frozen header row and a
+/// totals row; counts are summed, ratios (shown with decimals) aren't.
+#[cfg(feature = "xlsx")]
+fn write_xlsx(
+    stats: &[User],
+    cli: &Cli,
+    path: &std::path::Path,
+) -> Result<(), rust_xlsxwriter::XlsxError> {
+    use rust_xlsxwriter::{Color, Format as CellFormat, FormatBorder, Workbook};
+
+    let columns = columns(cli, false);
+    let header = CellFormat::new()
+        .set_bold()
+        .set_background_color(Color::Theme(4, 2))
+        .set_border_bottom(FormatBorder::Thin);
+    let integer = CellFormat::new().set_num_format("#,##0");
+    let decimal = CellFormat::new().set_num_format("#,##0.0###");
+    let footer = CellFormat::new()
+        .set_bold()
+        .set_border_top(FormatBorder::Double);
+    let footer_integer = footer.clone().set_num_format("#,##0");
+
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    for (col, column) in columns.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, column.title, &header)?;
+    }
+    let mut totals = vec![Some(0.0); columns.len()];
+    for (row, user) in stats.iter().enumerate() {
+        let row = row as u32 + 1;
+        for (col, column) in columns.iter().enumerate() {
+            let value = (column.value)(user);
+            match value.parse::<f64>() {
+                Ok(number) if column.numeric => {
+                    let is_count = !value.contains('.');
+                    let format = if is_count { &integer } else { &decimal };
+                    sheet.write_number_with_format(row, col as u16, number, format)?;
+                    totals[col] = totals[col].filter(|_| is_count).map(|sum| sum + number);
+                }
+                _ => {
+                    sheet.write_string(row, col as u16, value)?;
+                    totals[col] = None;
+                }
+            }
+        }
+    }
+    let row = stats.len() as u32 + 1;
+    for (col, total) in totals.into_iter().enumerate() {
+        let col = col as u16;
+        match total {
+            Some(total) if columns[col as usize].numeric => {
+                sheet.write_number_with_format(row, col, total, &footer_integer)?
+            }
+            _ if col == 0 => sheet.write_string_with_format(row, col, "Total", &footer)?,
+            _ => sheet.write_blank(row, col, &footer)?,
+        };
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofit();
+    workbook.save(path)
+}
+
+/// Counted commit of `--format ndjson-events`.
+#[derive(Serialize)]
+pub struct CommitEvent<'a> {
+    pub oid: String,
+    pub author: &'a str,
+    pub email: &'a str,
+    /// RFC 3339 time of the `--date` kind
+    pub time: String,
+    pub added: usize,
+    pub deleted: usize,
+    /// Changed files
+    pub files: usize,
+}
+
+/// Write one `--format ndjson-events` line.
+pub fn print_event(event: &CommitEvent, pretty: bool) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    write_json(&mut out, event, pretty)?;
+    writeln!(out)
+}
+
+/// Print the JSON Schema of `--format json`; a `--format jsonl` line is one
+/// of its `authors`.
+pub fn print_json_schema() -> io::Result<()> {
+    let schema = schema_for!(Report);
+    let mut out = io::stdout().lock();
+    write_json(&mut out, &schema, true)?;
+    writeln!(out)
+}
+
+/// Versions printed by `--version-json`.
+#[derive(Serialize)]
+struct VersionInfo {
+    name: &'static str,
+    version: &'static str,
+    libgit2: String,
+    git2: &'static str,
+}
+
+/// Print the versions of git-stats and the git library it was built with.
+pub fn print_version_json() -> io::Result<()> {
+    let git = git2::Version::get();
+    let (major, minor, rev) = git.libgit2_version();
+    let info = VersionInfo {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        libgit2: format!("{major}.{minor}.{rev}"),
+        git2: git.crate_version(),
+    };
+    let mut out = io::stdout().lock();
+    write_json(&mut out, &info, false)?;
+    writeln!(out)
+}
+
+pub fn write_json<W: Write + ?Sized, T: Serialize>(
+    out: &mut W,
+    value: &T,
+    pretty: bool,
+) -> io::Result<()> {
+    if pretty {
+        serde_json::to_writer_pretty(out, value)?;
+    } else {
+        serde_json::to_writer(out, value)?;
+    }
+    Ok(())
+}
+
+/// A column of the tabular formats.
+struct Column<'a> {
+    title: &'static str,
+    /// Right-aligned in markdown and asciidoc
+    numeric: bool,
+    value: Box<dyn Fn(&User) -> String + 'a>,
+}
+
+impl<'a> Column<'a> {
+    fn new(title: &'static str, numeric: bool, value: impl Fn(&User) -> String + 'a) -> Self {
+        Column {
+            title,
+            numeric,
+            value: Box::new(value),
+        }
+    }
+}
+
+/// Columns selected by the command line, in output order.
+///
+/// With `human` counts are abbreviated by [`compact`].
+fn columns(cli: &Cli, human: bool) -> Vec<Column<'_>> {
+    // With --fields every column is built, then picked in the given order.
+    let all = !cli.fields.is_empty();
+    let mut columns = Vec::new();
+    if let Some(m) = cli.module.as_deref().or(all.then_some("")) {
+        columns.push(Column::new("module", false, move |_| m.to_string()));
+    }
+    columns.push(Column::new("name", false, |u| u.name.clone()));
+    columns.push(Column::new("email", false, |u| u.email.clone()));
+    columns.push(Column::new("commits", true, move |u| {
+        count(u.commits, human)
+    }));
+    columns.push(Column::new("added", true, move |u| count(u.added, human)));
+    columns.push(Column::new("deleted", true, move |u| {
+        count(u.deleted, human)
+    }));
+    if all || cli.show_add_ratio {
+        columns.push(Column::new("add_ratio", true, |u| {
+            format!("{:.1}", u.add_ratio())
+        }));
+    }
+    if all || cli.show_median_commit {
+        columns.push(Column::new("median_commit_lines", true, |u| {
+            format!("{:.1}", u.median_commit_lines())
+        }));
+    }
+    if all || cli.show_first_last {
+        columns.push(Column::new("first_commit", false, |u| day(u.first)));
+        columns.push(Column::new("last_commit", false, |u| day(u.last)));
+    }
+    if all || cli.separate_lockfiles {
+        columns.push(Column::new("lockfile_added", true, move |u| {
+            count(u.lockfile_added, human)
+        }));
+        columns.push(Column::new("lockfile_deleted", true, move |u| {
+            count(u.lockfile_deleted, human)
+        }));
+    }
+    if all || cli.split_tests {
+        columns.push(Column::new("test_added", true, move |u| {
+            count(u.test_added, human)
+        }));
+        columns.push(Column::new("test_deleted", true, move |u| {
+            count(u.test_deleted, human)
+        }));
+        columns.push(Column::new("test_ratio", true, |u| {
+            format!("{:.1}", u.test_ratio())
+        }));
+    }
+    if all || cli.min_message_length.is_some() {
+        columns.push(Column::new("short_messages", true, move |u| {
+            count(u.short_messages, human)
+        }));
+    }
+    if all || cli.credit_reviewers {
+        columns.push(Column::new("reviews", true, move |u| {
+            count(u.reviews, human)
+        }));
+    }
+    if all || cli.refactor_threshold.is_some() {
+        columns.push(Column::new("refactor_commits", true, move |u| {
+            count(u.refactor_commits, human)
+        }));
+    }
+    if all || cli.show_mixed_commits {
+        columns.push(Column::new("mixed_commits", true, move |u| {
+            count(u.mixed_commits, human)
+        }));
+    }
+    if all || cli.count_all_commits_in_range {
+        columns.push(Column::new("all_commits", true, move |u| {
+            count(u.all_commits, human)
+        }));
+    }
+    if all || cli.count_prs {
+        columns.push(Column::new("prs", true, move |u| count(u.prs, human)));
+    }
+    if all || cli.weekend_split {
+        columns.push(Column::new("weekend_commits", true, move |u| {
+            count(u.weekend_commits, human)
+        }));
+        columns.push(Column::new("weekday_commits", true, move |u| {
+            count(u.weekday_commits, human)
+        }));
+        columns.push(Column::new("weekend_lines", true, move |u| {
+            count(u.weekend_lines, human)
+        }));
+        columns.push(Column::new("weekday_lines", true, move |u| {
+            count(u.weekday_lines, human)
+        }));
+    }
+    if all || cli.per_kloc {
+        columns.push(Column::new("churn_per_kloc", true, |u| {
+            format!("{:.1}", u.churn_per_kloc)
+        }));
+    }
+    if all || !cli.weight_path.is_empty() {
+        columns.push(Column::new("weighted_lines", true, |u| {
+            format!("{:.1}", u.weighted_lines)
+        }));
+    }
+    if all || cli.top_file_per_author {
+        columns.push(Column::new("top_file", false, |u| {
+            u.top_file()
+                .map_or(String::new(), |(path, _)| path.to_string())
+        }));
+        columns.push(Column::new("top_file_commits", true, move |u| {
+            count(u.top_file().map_or(0, |(_, n)| n), human)
+        }));
+    }
+    if all || cli.grep_lines.is_some() {
+        columns.push(Column::new("grep_added", true, move |u| {
+            count(u.grep_added, human)
+        }));
+        columns.push(Column::new("grep_deleted", true, move |u| {
+            count(u.grep_deleted, human)
+        }));
+    }
+    if all || cli.ext_breakdown {
+        columns.push(Column::new("extensions", false, |u| {
+            u.ext_breakdown(cli.ext_breakdown_limit)
+        }));
+    }
+    if all || cli.blame_weight.is_some() {
+        columns.push(Column::new("surviving", true, move |u| {
+            count(u.surviving, human)
+        }));
+        columns.push(Column::new("score", true, |u| format!("{:.4}", u.score)));
+    }
+    if !all {
+        return columns;
+    }
+    let mut picked = Vec::with_capacity(cli.fields.len());
+    for field in &cli.fields {
+        let name = field.to_possible_value().unwrap();
+        if let Some(idx) = columns.iter().position(|c| c.title == name.get_name()) {
+            picked.push(columns.remove(idx));
+        }
+    }
+    picked
+}
+
+/// Format a count, abbreviated by [`compact`] if `human` is set.
+fn count(n: usize, human: bool) -> String {
+    if human {
+        compact(n)
+    } else {
+        n.to_string()
+    }
+}
+
+/// Abbreviate a count to one decimal with a k/M/B suffix, e.g. `12.3k`.
+///
+/// Counts below 1000 are kept as is.
+fn compact(n: usize) -> String {
+    if n < 1000 {
+        return n.to_string();
+    }
+    let mut value = n as f64;
+    for suffix in ["k", "M", "B"] {
+        value /= 1000.0;
+        // Round first so 999_999 becomes 1.0M rather than 1000.0k.
+        if (value * 10.0).round() / 10.0 < 1000.0 || suffix == "B" {
+            return format!("{value:.1}{suffix}");
+        }
+    }
+    unreachable!()
+}
+
+fn write_text(out: &mut dyn Write, stats: &[User], cli: &Cli) -> io::Result<()> {
+    let sep = cli.separator.as_str();
+    let columns = columns(cli, cli.human);
+    let mut warned = false;
+    for user in stats {
+        if let Some(template) = cli.template.as_ref() {
+            writeln!(out, "{}", template.render(cli.module.as_deref(), user))?;
+        } else {
+            let row = columns.iter().map(|c| (c.value)(user)).collect::<Vec<_>>();
+            if !warned && !sep.is_empty() && row.iter().any(|field| field.contains(sep)) {
+                warn!("separator {sep:?} appears inside a field, output may be ambiguous");
+                warned = true;
+            }
+            writeln!(out, "{}{sep} {}", row.join(sep), summary(user))?;
+        }
+        if cli.with_commits {
+            for id in &user.commit_ids {
+                writeln!(out, "    {id}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_tsv(out: &mut dyn Write, stats: &[User], cli: &Cli) -> io::Result<()> {
+    let columns = columns(cli, false)
+        .into_iter()
+        .filter(|c| !cli.fields.is_empty() || c.title != "module")
+        .collect::<Vec<_>>();
+    for user in stats {
+        let mut row = columns
+            .iter()
+            .map(|c| (c.value)(user).replace(['\t', '\n'], " "))
+            .collect::<Vec<_>>();
+        if cli.verbose_text {
+            row.push(summary(user));
+        }
+        writeln!(out, "{}", row.join("\t"))?;
+    }
+    Ok(())
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break.
+pub fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Escape a markdown or asciidoc table cell.
+fn escape_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+/// Percent-encode a URL query value.
+fn encode_query(s: &str) -> String {
+    let mut out = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'@' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+/// Commits of the author under `--repo-url`, e.g. on GitHub.
+fn author_url(cli: &Cli, user: &User) -> Option<String> {
+    let base = cli.repo_url.as_deref()?.trim_end_matches('/');
+    Some(format!(
+        "{base}/commits?author={}",
+        encode_query(&user.email)
+    ))
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_html(out: &mut dyn Write, stats: &[User], cli: &Cli) -> io::Result<()> {
+    let columns = columns(cli, cli.human);
+    writeln!(out, "<table>")?;
+    writeln!(out, "  <thead>")?;
+    writeln!(out, "    <tr>")?;
+    for column in &columns {
+        writeln!(out, "      <th>{}</th>", column.title)?;
+    }
+    writeln!(out, "    </tr>")?;
+    writeln!(out, "  </thead>")?;
+    writeln!(out, "  <tbody>")?;
+    for user in stats {
+        writeln!(out, "    <tr>")?;
+        for column in &columns {
+            let value = escape_html(&(column.value)(user));
+            let value = match author_url(cli, user) {
+                Some(url) if column.title == "name" => {
+                    format!("<a href=\"{}\">{value}</a>", escape_html(&url))
+                }
+                _ => value,
+            };
+            if column.numeric {
+                writeln!(out, "      <td align=\"right\">{value}</td>")?;
+            } else {
+                writeln!(out, "      <td>{value}</td>")?;
+            }
+        }
+        writeln!(out, "    </tr>")?;
+    }
+    writeln!(out, "  </tbody>")?;
+    writeln!(out, "</table>")?;
+    Ok(())
+}
+
+fn write_markdown(out: &mut dyn Write, stats: &[User], cli: &Cli) -> io::Result<()> {
+    let columns = columns(cli, cli.human);
+    let titles = columns.iter().map(|c| c.title).collect::<Vec<_>>();
+    writeln!(out, "| {} |", titles.join(" | "))?;
+    let rules = columns
+        .iter()
+        .map(|c| if c.numeric { "---:" } else { "---" })
+        .collect::<Vec<_>>();
+    writeln!(out, "| {} |", rules.join(" | "))?;
+    for user in stats {
+        let row = columns
+            .iter()
+            .map(|c| {
+                let value = escape_cell(&(c.value)(user));
+                match author_url(cli, user) {
+                    Some(url) if c.title == "name" => format!("[{value}]({url})"),
+                    _ => value,
+                }
+            })
+            .collect::<Vec<_>>();
+        writeln!(out, "| {} |", row.join(" | "))?;
+    }
+    Ok(())
+}
+
+fn write_asciidoc(out: &mut dyn Write, stats: &[User], cli: &Cli) -> io::Result<()> {
+    let columns = columns(cli, cli.human);
+    let cols = columns
+        .iter()
+        .map(|c| if c.numeric { ">1" } else { "1" })
+        .collect::<Vec<_>>();
+    writeln!(out, "[cols=\"{}\",options=\"header\"]", cols.join(","))?;
+    writeln!(out, "|===")?;
+    let titles = columns.iter().map(|c| c.title).collect::<Vec<_>>();
+    writeln!(out, "|{}", titles.join(" |"))?;
+    for user in stats {
+        let row = columns
+            .iter()
+            .map(|c| escape_cell(&(c.value)(user)))
+            .collect::<Vec<_>>();
+        writeln!(out)?;
+        writeln!(out, "|{}", row.join(" |"))?;
+    }
+    writeln!(out, "|===")?;
+    Ok(())
+}
+
+/// Write `git_stats,author=<name>,repo=<label> commits=<n>i,added=<n>i,deleted=<n>i <ns>`
+/// points, timestamped with the author's last commit.
+///
+/// `repo` is left out without a [`repo_label`].
+fn write_influx(out: &mut dyn Write, stats: &[User], cli: &Cli) -> io::Result<()> {
+    let repo = repo_label(cli)
+        .map(|r| format!(",repo={}", escape_tag(&r)))
+        .unwrap_or_default();
+    for user in stats {
+        // Empty tag values are invalid, the point goes untagged then.
+        let author = if user.name.is_empty() {
+            String::new()
+        } else {
+            format!(",author={}", escape_tag(&user.name))
+        };
+        // Without commits the point takes the time it's written at.
+        let time = user.last.map_or(String::new(), |last| {
+            format!(" {}", last.timestamp_nanos_opt().unwrap_or_default())
+        });
+        writeln!(
+            out,
+            "git_stats{author}{repo} commits={}i,added={}i,deleted={}i{time}",
+            user.commits, user.added, user.deleted,
+        )?;
+    }
+    Ok(())
+}
+
+/// Write `git_stats_{commits,added,deleted}{author="<name>",repo="<label>"} <n>`
+/// gauges, the `repo` label being left out without a [`repo_label`].
+fn write_prometheus(out: &mut dyn Write, stats: &[User], cli: &Cli) -> io::Result<()> {
+    let repo = repo_label(cli)
+        .map(|r| format!(",repo=\"{}\"", escape_label(&r)))
+        .unwrap_or_default();
+    for (metric, help) in [
+        ("commits", "Commits per author."),
+        ("added", "Lines added per author."),
+        ("deleted", "Lines deleted per author."),
+    ] {
+        writeln!(out, "# HELP git_stats_{metric} {help}")?;
+        writeln!(out, "# TYPE git_stats_{metric} gauge")?;
+        for user in stats {
+            writeln!(
+                out,
+                "git_stats_{metric}{{author=\"{}\"{repo}}} {}",
+                escape_label(&user.name),
+                match metric {
+                    "commits" => user.commits,
+                    "added" => user.added,
+                    _ => user.deleted,
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Escape a Prometheus label value: backslashes, quotes and line breaks.
+fn escape_label(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Repository label of the metrics formats: the --module, or the repository's
+/// directory name when only one is analyzed.
+fn repo_label(cli: &Cli) -> Option<String> {
+    let label = match (cli.module.as_deref(), cli.repository.as_slice()) {
+        (Some(module), _) => Some(module.to_string()),
+        (None, []) => repo_name("."),
+        (None, [path]) => repo_name(path),
+        (None, _) => None,
+    };
+    label.filter(|l| !l.is_empty())
+}
+
+/// Directory name of a repository path.
+fn repo_name(path: &str) -> Option<String> {
+    let path = fs::canonicalize(path).ok()?;
+    Some(path.file_name()?.to_string_lossy().into_owned())
+}
+
+/// Escape a line protocol tag value: commas, equals signs and spaces.
+fn escape_tag(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            ',' | '=' | ' ' => {
+                out.push('\\');
+                out.push(c);
+            }
+            // Line breaks would end the point.
+            '\n' | '\r' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `YYYY-MM-DD` of a commit time, empty without one.
+pub fn day(time: Option<DateTime<Local>>) -> String {
+    time.map_or(String::new(), |t| t.format("%Y-%m-%d").to_string())
+}
+
+/// Summary sentence of the text format, dated from the first commit.
+fn summary(user: &User) -> String {
+    let User {
+        first,
+        commits,
+        added,
+        deleted,
+        ..
+    } = user;
+    let since = first.map_or(String::new(), |first| {
+        format!("从 {} 年 {:02} 月至今，", first.year(), first.month())
+    });
+    format!("{since}共提交 commit {commits} 个， 新增代码 {added} 行, 删除代码 {deleted} 行")
+}
+
+fn write_shortlog(out: &mut dyn Write, stats: &[User], with_email: bool) -> io::Result<()> {
+    let mut stats = stats.iter().collect::<Vec<_>>();
+    stats.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.name.cmp(&b.name)));
+    for user in stats {
+        if with_email {
+            writeln!(out, "{:>6}\t{} <{}>", user.commits, user.name, user.email)?;
+        } else {
+            writeln!(out, "{:>6}\t{}", user.commits, user.name)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn file_stem_stays_inside_the_directory() {
+        for identity in ["../../x", "/etc/passwd", "..", "a/../../b", "C:\\x"] {
+            let user = User::new(identity.into(), String::new());
+            let stem = file_stem(&user);
+            assert!(!stem.contains(['/', '\\']), "{identity}: {stem}");
+            assert!(!stem.starts_with('.'), "{identity}: {stem}");
+            let path = Path::new("out").join(format!("{stem}.txt"));
+            assert_eq!(path.parent(), Some(Path::new("out")), "{identity}");
+        }
+        let user = User::new("Alice".into(), "../alice@example.com".into());
+        assert_eq!(file_stem(&user), "_alice@example.com");
+    }
+
+    #[test]
+    fn summary_pads_month_of_first_commit() {
+        let march = Local.with_ymd_and_hms(2024, 3, 9, 12, 0, 0).unwrap();
+        let january = Local.with_ymd_and_hms(2024, 1, 5, 10, 0, 0).unwrap();
+        let mut user = User::new("Alice".into(), "alice@example.com".into());
+        user.add_commit(march, 1, 0);
+        user.add_commit(january, 10, 2);
+
+        assert_eq!(
+            summary(&user),
+            "从 2024 年 01 月至今，共提交 commit 2 个， 新增代码 11 行, 删除代码 2 行"
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use git2::{ObjectType, Pathspec, PathspecFlags, Repository, TreeWalkMode, TreeWalkResult};
+
+use std::path::Path;
+
+/// Count the lines of every text file in HEAD matching `globs`.
+///
+/// Reads each blob of the tree once, so it costs about as much as a checkout
+/// of HEAD.
+pub fn head_lines(repo: &Repository, globs: &[String]) -> Result<usize, git2::Error> {
+    let tree = repo.head()?.peel_to_tree()?;
+    let pathspec = if globs.is_empty() {
+        None
+    } else {
+        Some(Pathspec::new(globs)?)
+    };
+
+    let mut blobs = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            let path = format!("{dir}{}", entry.name().unwrap_or(""));
+            let matched = pathspec
+                .as_ref()
+                .is_none_or(|spec| spec.matches_path(Path::new(&path), PathspecFlags::DEFAULT));
+            if matched {
+                blobs.push(entry.id());
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+
+    let mut lines = 0;
+    for id in blobs {
+        let blob = repo.find_blob(id)?;
+        if blob.is_binary() {
+            continue;
+        }
+        let content = blob.content();
+        lines += content.iter().filter(|&&b| b == b'\n').count();
+        if content.last().is_some_and(|&b| b != b'\n') {
+            lines += 1;
+        }
+    }
+    Ok(lines)
+}
@@ -0,0 +1,130 @@
+use chrono::Datelike;
+
+use crate::{output, User};
+
+/// Placeholder used in `--template` strings.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Field {
+    /// `%n`: author name
+    Name,
+    /// `%e`: author e-mail
+    Email,
+    /// `%c`: commits
+    Commits,
+    /// `%a`: added lines
+    Added,
+    /// `%d`: deleted lines
+    Deleted,
+    /// `%m`: month of the author's first commit
+    Month,
+    /// `%y`: year of the author's first commit
+    Year,
+    /// `%r`: added / (added + deleted) in percent
+    AddRatio,
+    /// `%f`: first commit date, `YYYY-MM-DD`
+    FirstCommit,
+    /// `%l`: last commit date, `YYYY-MM-DD`
+    LastCommit,
+    /// `%M`: module name given by `--module`
+    Module,
+}
+
+impl Field {
+    fn from_placeholder(c: char) -> Option<Field> {
+        Some(match c {
+            'n' => Field::Name,
+            'e' => Field::Email,
+            'c' => Field::Commits,
+            'a' => Field::Added,
+            'd' => Field::Deleted,
+            'm' => Field::Month,
+            'y' => Field::Year,
+            'r' => Field::AddRatio,
+            'f' => Field::FirstCommit,
+            'l' => Field::LastCommit,
+            'M' => Field::Module,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+/// Parsed `--template` string.
+#[derive(Clone, Debug)]
+pub struct Template(Vec<Segment>);
+
+/// Parse a template like `%n\t%e\t%c`.
+///
+/// `%%` is a literal percent, and `\t`, `\n`, `\\` are unescaped so the
+/// template can be passed in plain shell quotes.
+pub fn parse_template(s: &str) -> Result<Template, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => match chars.next() {
+                Some('%') => literal.push('%'),
+                Some(p) => {
+                    let field = Field::from_placeholder(p)
+                        .ok_or_else(|| format!("unknown placeholder: %{p}"))?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+                None => return Err("dangling % at end of template".to_string()),
+            },
+            '\\' => match chars.next() {
+                Some('t') => literal.push('\t'),
+                Some('n') => literal.push('\n'),
+                Some('\\') => literal.push('\\'),
+                Some(other) => {
+                    literal.push('\\');
+                    literal.push(other);
+                }
+                None => literal.push('\\'),
+            },
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(Template(segments))
+}
+
+impl Template {
+    /// Render one author row.
+    pub fn render(&self, module: Option<&str>, user: &User) -> String {
+        let mut out = String::new();
+        for segment in &self.0 {
+            match segment {
+                Segment::Literal(s) => out.push_str(s),
+                Segment::Field(field) => {
+                    match field {
+                        Field::Name => out.push_str(&user.name),
+                        Field::Email => out.push_str(&user.email),
+                        Field::Commits => out.push_str(&user.commits.to_string()),
+                        Field::Added => out.push_str(&user.added.to_string()),
+                        Field::Deleted => out.push_str(&user.deleted.to_string()),
+                        Field::Month => out
+                            .push_str(&user.first.map_or(String::new(), |t| t.month().to_string())),
+                        Field::Year => out
+                            .push_str(&user.first.map_or(String::new(), |t| t.year().to_string())),
+                        Field::AddRatio => out.push_str(&format!("{:.1}", user.add_ratio())),
+                        Field::FirstCommit => out.push_str(&output::day(user.first)),
+                        Field::LastCommit => out.push_str(&output::day(user.last)),
+                        Field::Module => out.push_str(module.unwrap_or("")),
+                    }
+                }
+            }
+        }
+        out
+    }
+}
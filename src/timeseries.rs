@@ -0,0 +1,122 @@
+use chrono::{DateTime, Local, NaiveDate};
+use clap::ValueEnum;
+use git2::Repository;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+
+use crate::output::csv_field;
+use crate::{DateKind, Totals};
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum Period {
+    /// YYYY-MM-DD
+    Day,
+    /// ISO week, YYYY-Www
+    Week,
+    /// YYYY-MM
+    Month,
+    /// YYYY
+    Year,
+}
+
+impl Period {
+    /// Label of the period containing `time`; labels sort chronologically.
+    pub fn key(self, time: DateTime<Local>) -> String {
+        let fmt = match self {
+            Period::Day => "%Y-%m-%d",
+            Period::Week => "%G-W%V",
+            Period::Month => "%Y-%m",
+            Period::Year => "%Y",
+        };
+        time.format(fmt).to_string()
+    }
+}
+
+/// Tag names grouped by the period of the commit they point to.
+pub fn tags_by_period(
+    repo: &Repository,
+    period: Period,
+    date: DateKind,
+) -> Result<HashMap<String, Vec<String>>, git2::Error> {
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for name in repo.tag_names(None)?.iter().flatten() {
+        let Ok(commit) = repo
+            .revparse_single(&format!("refs/tags/{name}"))
+            .and_then(|obj| obj.peel_to_commit())
+        else {
+            continue;
+        };
+        tags.entry(period.key(date.time_of(&commit)))
+            .or_default()
+            .push(name.to_string());
+    }
+    Ok(tags)
+}
+
+/// Write `period\tcommits\tadded\tdeleted[\ttags]` rows, oldest first.
+pub fn write(
+    out: &mut dyn Write,
+    series: HashMap<String, Totals>,
+    tags: &HashMap<String, Vec<String>>,
+    annotate_tags: bool,
+) -> io::Result<()> {
+    let mut rows = series.into_iter().collect::<Vec<_>>();
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+    for (key, totals) in rows {
+        let Totals {
+            commits,
+            added,
+            deleted,
+        } = totals;
+        if annotate_tags {
+            let names = tags.get(&key).map(|t| t.join(",")).unwrap_or_default();
+            writeln!(out, "{key}\t{commits}\t{added}\t{deleted}\t{names}")?;
+        } else {
+            writeln!(out, "{key}\t{commits}\t{added}\t{deleted}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Write `date,author,commits,added,deleted` rows keyed by (day, author),
+/// by date then author.
+///
+/// With `fill_gaps` every author gets a row for each day from the first to
+/// the last commit day, with zeros where they didn't commit.
+pub fn write_csv(
+    out: &mut dyn Write,
+    timeline: HashMap<(String, String), Totals>,
+    fill_gaps: bool,
+) -> io::Result<()> {
+    let mut rows = timeline.into_iter().collect::<BTreeMap<_, _>>();
+    if fill_gaps {
+        let authors = rows.keys().map(|(_, a)| a.clone()).collect::<BTreeSet<_>>();
+        let days = rows
+            .keys()
+            .filter_map(|(d, _)| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .collect::<BTreeSet<_>>();
+        if let (Some(&first), Some(&last)) = (days.first(), days.last()) {
+            for day in first.iter_days().take_while(|d| *d <= last) {
+                let day = day.format("%Y-%m-%d").to_string();
+                for author in &authors {
+                    rows.entry((day.clone(), author.clone())).or_default();
+                }
+            }
+        }
+    }
+    writeln!(out, "date,author,commits,added,deleted")?;
+    for ((day, author), totals) in rows {
+        let Totals {
+            commits,
+            added,
+            deleted,
+        } = totals;
+        writeln!(
+            out,
+            "{day},{},{commits},{added},{deleted}",
+            csv_field(&author)
+        )?;
+    }
+    Ok(())
+}
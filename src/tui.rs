@@ -0,0 +1,217 @@
+use chrono::{DateTime, Local};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::error::AppError;
+use crate::{parse_time, sort_users, Cli, Order, SortBy, User};
+
+/// Which end of the date range is being typed in.
+#[derive(Copy, Clone)]
+enum Bound {
+    Since,
+    Until,
+}
+
+struct App {
+    stats: Vec<User>,
+    sort_by: SortBy,
+    order: Order,
+    since: Option<DateTime<Local>>,
+    until: Option<DateTime<Local>>,
+    table: TableState,
+    /// Date being typed after `s` or `u`
+    input: Option<(Bound, String)>,
+    status: String,
+}
+
+const HELP: &str =
+    "sort: n name  e email  c commits  a added  d deleted  r ratio  m median  o order | \
+                    range: s since  u until | q quit";
+
+/// Show `stats` until the user quits; `rescan` walks the repository again
+/// when a new date range is entered.
+pub fn run(
+    stats: Vec<User>,
+    cli: &Cli,
+    rescan: impl FnMut(Option<DateTime<Local>>, Option<DateTime<Local>>) -> Result<Vec<User>, AppError>,
+) -> Result<(), AppError> {
+    let mut app = App {
+        stats,
+        sort_by: cli.sort_by,
+        order: cli.order,
+        since: cli.since,
+        until: cli.until,
+        table: TableState::default().with_selected(Some(0)),
+        input: None,
+        status: HELP.to_string(),
+    };
+    app.sort();
+    let mut terminal = ratatui::try_init()?;
+    let result = app.run(&mut terminal, rescan);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn sort(&mut self) {
+        sort_users(&mut self.stats, self.sort_by, self.order, false);
+    }
+
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        mut rescan: impl FnMut(
+            Option<DateTime<Local>>,
+            Option<DateTime<Local>>,
+        ) -> Result<Vec<User>, AppError>,
+    ) -> Result<(), AppError> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some((bound, mut text)) = self.input.take() {
+                match key.code {
+                    KeyCode::Enter => self.apply(bound, &text, &mut rescan),
+                    KeyCode::Esc => self.status = HELP.to_string(),
+                    KeyCode::Backspace => {
+                        text.pop();
+                        self.input = Some((bound, text));
+                    }
+                    KeyCode::Char(c) => {
+                        text.push(c);
+                        self.input = Some((bound, text));
+                    }
+                    _ => self.input = Some((bound, text)),
+                }
+                continue;
+            }
+            let sort_by = match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.table.select_next();
+                    continue;
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.table.select_previous();
+                    continue;
+                }
+                KeyCode::Char('o') => {
+                    self.order = match self.order {
+                        Order::Asc => Order::Desc,
+                        Order::Desc => Order::Asc,
+                    };
+                    self.sort();
+                    continue;
+                }
+                KeyCode::Char('s') => {
+                    self.input = Some((Bound::Since, String::new()));
+                    continue;
+                }
+                KeyCode::Char('u') => {
+                    self.input = Some((Bound::Until, String::new()));
+                    continue;
+                }
+                KeyCode::Char('n') => SortBy::Name,
+                KeyCode::Char('e') => SortBy::Email,
+                KeyCode::Char('c') => SortBy::Commits,
+                KeyCode::Char('a') => SortBy::Added,
+                KeyCode::Char('d') => SortBy::Deleted,
+                KeyCode::Char('r') => SortBy::AddRatio,
+                KeyCode::Char('m') => SortBy::MedianCommit,
+                _ => continue,
+            };
+            self.sort_by = sort_by;
+            self.sort();
+        }
+    }
+
+    /// Parse an entered date, empty for unbounded, and rescan with it.
+    fn apply(
+        &mut self,
+        bound: Bound,
+        text: &str,
+        rescan: &mut impl FnMut(
+            Option<DateTime<Local>>,
+            Option<DateTime<Local>>,
+        ) -> Result<Vec<User>, AppError>,
+    ) {
+        let time = match text.trim() {
+            "" => None,
+            text => match parse_time(text) {
+                Ok(time) => Some(time),
+                Err(e) => {
+                    self.status = format!("{text}: {e}");
+                    return;
+                }
+            },
+        };
+        let (since, until) = match bound {
+            Bound::Since => (time, self.until),
+            Bound::Until => (self.since, time),
+        };
+        match rescan(since, until) {
+            Ok(stats) => {
+                self.stats = stats;
+                self.since = since;
+                self.until = until;
+                self.sort();
+                self.table.select(Some(0));
+                self.status = HELP.to_string();
+            }
+            Err(e) => self.status = e.to_string(),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [table_area, status_area] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+        let range = |time: Option<DateTime<Local>>| {
+            time.map_or("-".to_string(), |t| t.format("%Y-%m-%d %H:%M").to_string())
+        };
+        let title = format!(
+            " {} authors, {} .. {} ",
+            self.stats.len(),
+            range(self.since),
+            range(self.until)
+        );
+        let header = Row::new(["name", "email", "commits", "added", "deleted"])
+            .style(Style::new().add_modifier(Modifier::BOLD));
+        let rows = self.stats.iter().map(|u| {
+            Row::new([
+                u.name.clone(),
+                u.email.clone(),
+                u.commits.to_string(),
+                u.added.to_string(),
+                u.deleted.to_string(),
+            ])
+        });
+        let widths = [
+            Constraint::Fill(2),
+            Constraint::Fill(3),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::bordered().title(title))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let status = match &self.input {
+            Some((Bound::Since, text)) => format!("since (empty for none): {text}"),
+            Some((Bound::Until, text)) => format!("until (empty for none): {text}"),
+            None => self.status.clone(),
+        };
+        frame.render_widget(Paragraph::new(Line::from(status)), status_area);
+    }
+}
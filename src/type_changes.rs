@@ -0,0 +1,53 @@
+use git2::{Delta, Diff};
+
+use std::io::{self, Write};
+
+/// A file of a commit that changed type, see `--report-type-changes`.
+pub struct TypeChange {
+    pub commit: String,
+    pub author: String,
+    pub path: String,
+    /// `text->binary`, `binary->text` or `typechange` for a file/symlink/submodule change
+    pub change: &'static str,
+}
+
+/// Paths of a diff that changed type, with the kind of change.
+///
+/// Text and binary are told apart by `DiffFile` flags, which libgit2 only
+/// fills in once the contents were loaded, i.e. after computing line stats.
+pub fn detect(diff: &Diff) -> Vec<(String, &'static str)> {
+    diff.deltas()
+        .filter_map(|delta| {
+            let (old, new) = (delta.old_file(), delta.new_file());
+            let change = match delta.status() {
+                Delta::Typechange => "typechange",
+                Delta::Modified | Delta::Renamed | Delta::Copied => {
+                    if old.is_not_binary() && new.is_binary() {
+                        "text->binary"
+                    } else if old.is_binary() && new.is_not_binary() {
+                        "binary->text"
+                    } else {
+                        return None;
+                    }
+                }
+                _ => return None,
+            };
+            let path = new.path().or_else(|| old.path())?;
+            Some((path.to_string_lossy().into_owned(), change))
+        })
+        .collect()
+}
+
+/// Write `commit\tauthor\tpath\tchange` rows in walk order.
+pub fn write(out: &mut dyn Write, changes: &[TypeChange]) -> io::Result<()> {
+    for TypeChange {
+        commit,
+        author,
+        path,
+        change,
+    } in changes
+    {
+        writeln!(out, "{commit}\t{author}\t{path}\t{change}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use chrono::Timelike;
+
+use std::io::{self, Write};
+
+use crate::User;
+
+/// Write mean/median commit hour and the share of commits outside
+/// `[start, end)` for each author.
+pub fn write(out: &mut dyn Write, stats: &[User], start: u32, end: u32) -> io::Result<()> {
+    for user in stats {
+        if user.times.is_empty() {
+            continue;
+        }
+        let mut hours: Vec<f64> = user
+            .times
+            .iter()
+            .map(|t| t.hour() as f64 + t.minute() as f64 / 60.0)
+            .collect();
+        hours.sort_by(f64::total_cmp);
+        let mean = hours.iter().sum::<f64>() / hours.len() as f64;
+        let mid = hours.len() / 2;
+        let median = if hours.len().is_multiple_of(2) {
+            (hours[mid - 1] + hours[mid]) / 2.0
+        } else {
+            hours[mid]
+        };
+        let off = user
+            .times
+            .iter()
+            .filter(|t| t.hour() < start || t.hour() >= end)
+            .count();
+        let off_ratio = off as f64 / user.times.len() as f64 * 100.0;
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{mean:.1}\t{median:.1}\t{off_ratio:.1}%",
+            user.name, user.email, user.commits,
+        )?;
+    }
+    Ok(())
+}